@@ -12,6 +12,7 @@ use std::{
     collections::HashMap,
     path::{Path, PathBuf},
     sync::Arc,
+    time::Duration,
 };
 use sword::prelude::*;
 use tokio::{fs, process::Command};
@@ -19,6 +20,9 @@ use uuid::Uuid;
 
 pub use preprocessor::RepositoriesPreprocessor;
 
+const DOCS_JOB_POLL_INTERVAL: Duration = Duration::from_secs(15);
+const DOCS_JOB_MAX_POLL_ERRORS: u32 = 5;
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[config(key = "repositories")]
 pub struct RepositoriesConfig {
@@ -70,15 +74,83 @@ impl RepositoriesService {
         let mailer = self.mailer.clone();
 
         tokio::spawn(async move {
-            let _ = mailer.send(mail).await;
-            let _ = wiki_client
-                .request_docs_gen(&repo.id, clone_path_str, repository_tree)
-                .await;
+            if let Err(e) = mailer.send(mail).await {
+                tracing::error!(
+                    "Failed to send doc-gen mail for repository {}: {e}",
+                    repo.id
+                );
+            }
+
+            let job_id = match wiki_client
+                .request_docs_gen(
+                    &repo.id,
+                    clone_path_str,
+                    repository_tree,
+                    &repo.last_commit_sha,
+                )
+                .await
+            {
+                Ok(job_id) => job_id,
+                Err(e) => {
+                    tracing::error!(
+                        "Docs generation could not be requested for repository {}: {e}",
+                        repo.id
+                    );
+                    return;
+                }
+            };
+
+            Self::watch_docs_job(&wiki_client, &repo.id, &job_id).await;
         });
 
         Ok(repo.id)
     }
 
+    /// Polls the wiki service until the job finishes and logs its outcome.
+    async fn watch_docs_job(wiki_client: &WikiClient, repository_id: &Uuid, job_id: &Uuid) {
+        let mut consecutive_errors = 0;
+
+        loop {
+            tokio::time::sleep(DOCS_JOB_POLL_INTERVAL).await;
+
+            let status = match wiki_client.docs_gen_status(job_id).await {
+                Ok(status) => status,
+                Err(e) => {
+                    consecutive_errors += 1;
+                    tracing::warn!("Failed to fetch status of docs generation job {job_id}: {e}");
+
+                    if consecutive_errors >= DOCS_JOB_MAX_POLL_ERRORS {
+                        tracing::error!(
+                            "Giving up on docs generation job {job_id} for repository {repository_id}"
+                        );
+                        return;
+                    }
+
+                    continue;
+                }
+            };
+
+            consecutive_errors = 0;
+
+            if !status.is_finished() {
+                continue;
+            }
+
+            if status.phase == "failed" {
+                tracing::error!(
+                    "Docs generation job {job_id} for repository {repository_id} failed: {}",
+                    status.error.unwrap_or_default()
+                );
+            } else {
+                tracing::info!(
+                    "Docs generation job {job_id} for repository {repository_id} finished"
+                );
+            }
+
+            return;
+        }
+    }
+
     async fn get_or_create(
         &self,
         owner_id: &Uuid,
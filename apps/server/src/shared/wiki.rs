@@ -13,6 +13,23 @@ pub struct WikiConfig {
     pub api_key: String,
 }
 
+#[derive(Debug, Deserialize)]
+struct DocsGenJob {
+    job_id: Uuid,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DocsGenJobStatus {
+    pub phase: String,
+    pub error: Option<String>,
+}
+
+impl DocsGenJobStatus {
+    pub fn is_finished(&self) -> bool {
+        matches!(self.phase.as_str(), "done" | "failed")
+    }
+}
+
 #[injectable(provider)]
 pub struct WikiClient {
     http_client: reqwest::Client,
@@ -32,13 +49,15 @@ impl WikiClient {
         repository_id: &Uuid,
         repository_clone_path: String,
         repository_tree: String,
-    ) -> AppResult<()> {
+        commit_sha: &str,
+    ) -> AppResult<Uuid> {
         let url = format!("{}/docs-gen", self.config.service_url);
 
         let body = json!({
             "repoId": repository_id.to_string(),
             "repoPath": repository_clone_path,
             "repoTree": repository_tree,
+            "commitSha": commit_sha,
         });
 
         let response = self
@@ -63,7 +82,41 @@ impl WikiClient {
             )));
         }
 
-        tracing::info!("Successfully requested docs generation for repository {repository_id}");
-        Ok(())
+        let job = response.json::<DocsGenJob>().await.map_err(|e| {
+            tracing::error!("Failed to parse docs-gen response from wiki service: {e}");
+            AppError::WikiService(format!("Invalid response: {e}"))
+        })?;
+
+        tracing::info!(
+            "Docs generation job {} queued for repository {repository_id}",
+            job.job_id
+        );
+
+        Ok(job.job_id)
+    }
+
+    pub async fn docs_gen_status(&self, job_id: &Uuid) -> AppResult<DocsGenJobStatus> {
+        let url = format!("{}/jobs/{job_id}", self.config.service_url);
+
+        let response = self
+            .http_client
+            .get(&url)
+            .header("Authorization", format!("Bearer {}", self.config.api_key))
+            .send()
+            .await
+            .map_err(|e| AppError::WikiService(format!("Request failed: {e}")))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_body: String = response.text().await.unwrap_or_default();
+            return Err(AppError::WikiService(format!(
+                "HTTP {status}: {error_body}"
+            )));
+        }
+
+        response
+            .json::<DocsGenJobStatus>()
+            .await
+            .map_err(|e| AppError::WikiService(format!("Invalid response: {e}")))
     }
 }
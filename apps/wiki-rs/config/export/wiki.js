@@ -0,0 +1,104 @@
+;(function () {
+	"use strict"
+
+	var index = window.WIKI_SEARCH_INDEX || []
+	var input = document.getElementById("search")
+	var results = document.getElementById("search-results")
+	var nav = document.getElementById("wiki-nav")
+	// Search results link from the site root; nested pages climb back to it.
+	var root = document.body.getAttribute("data-root") || ""
+
+	function tokenize(value) {
+		return value
+			.toLowerCase()
+			.split(/[^\p{L}\p{N}_]+/u)
+			.filter(function (token) {
+				return token.length > 1
+			})
+	}
+
+	function score(entry, tokens) {
+		var title = entry.title.toLowerCase()
+		var text = entry.text.toLowerCase()
+		var total = 0
+
+		for (var i = 0; i < tokens.length; i++) {
+			var inTitle = title.indexOf(tokens[i]) !== -1
+			var inText = text.indexOf(tokens[i]) !== -1
+			if (!inTitle && !inText) {
+				return 0
+			}
+			total += (inTitle ? 5 : 0) + (inText ? 1 : 0)
+		}
+
+		return total
+	}
+
+	function snippet(text, token) {
+		var position = text.toLowerCase().indexOf(token)
+		if (position === -1) {
+			return text.slice(0, 120)
+		}
+		var start = Math.max(0, position - 50)
+		return (start > 0 ? "…" : "") + text.slice(start, position + 70) + "…"
+	}
+
+	function render(query) {
+		var tokens = tokenize(query)
+		results.innerHTML = ""
+		nav.hidden = tokens.length > 0
+
+		if (tokens.length === 0) {
+			return
+		}
+
+		var matches = index
+			.map(function (entry) {
+				return { entry: entry, score: score(entry, tokens) }
+			})
+			.filter(function (match) {
+				return match.score > 0
+			})
+			.sort(function (a, b) {
+				return b.score - a.score
+			})
+			.slice(0, 20)
+
+		if (matches.length === 0) {
+			var empty = document.createElement("li")
+			empty.textContent = "Sin resultados"
+			results.appendChild(empty)
+			return
+		}
+
+		matches.forEach(function (match) {
+			var item = document.createElement("li")
+			var link = document.createElement("a")
+			var detail = document.createElement("span")
+
+			link.href = root + match.entry.url
+			link.textContent = match.entry.title
+			detail.className = "snippet"
+			detail.textContent = snippet(match.entry.text, tokens[0])
+
+			link.appendChild(detail)
+			item.appendChild(link)
+			results.appendChild(item)
+		})
+	}
+
+	if (input && results && nav) {
+		input.addEventListener("input", function () {
+			render(input.value)
+		})
+	}
+
+	if (window.hljs) {
+		window.hljs.highlightAll()
+	}
+
+	if (window.mermaid) {
+		window.mermaid.initialize({ startOnLoad: false })
+		window.mermaid.run({ querySelector: "pre.mermaid" })
+	}
+})()
@@ -0,0 +1,89 @@
+use crate::config::GenerationParams;
+use crate::llm::{ChatStreamEvent, LlmClient};
+use crate::models::RetrievedChunk;
+use crate::prompts::PromptStore;
+use anyhow::Result;
+use futures::stream::BoxStream;
+
+#[derive(Clone)]
+pub struct AnswerAgent {
+    llm: LlmClient,
+    prompts: PromptStore,
+    params: GenerationParams,
+}
+
+impl AnswerAgent {
+    pub fn new(llm: LlmClient, prompts: PromptStore, params: GenerationParams) -> Self {
+        Self {
+            llm,
+            prompts,
+            params,
+        }
+    }
+
+    pub async fn run(&self, question: &str, chunks: &[RetrievedChunk]) -> Result<String> {
+        let prompt = self.render_prompt(question, chunks)?;
+        let answer = self
+            .llm
+            .chat(
+                &prompt,
+                self.params.temperature,
+                Some(self.params.max_tokens),
+            )
+            .await?;
+        Ok(answer.trim().to_string())
+    }
+
+    pub async fn run_stream(
+        &self,
+        question: &str,
+        chunks: &[RetrievedChunk],
+    ) -> Result<BoxStream<'static, Result<ChatStreamEvent>>> {
+        let prompt = self.render_prompt(question, chunks)?;
+        self.llm
+            .chat_stream(
+                &prompt,
+                self.params.temperature,
+                Some(self.params.max_tokens),
+            )
+            .await
+    }
+
+    fn render_prompt(&self, question: &str, chunks: &[RetrievedChunk]) -> Result<String> {
+        self.prompts.render(
+            "answer/answer",
+            &[
+                ("question", question.to_string()),
+                ("sources", build_numbered_sources(chunks)),
+            ],
+        )
+    }
+}
+
+/// Sources are numbered from 1 so the model can cite them as `[n]`.
+pub fn build_numbered_sources(chunks: &[RetrievedChunk]) -> String {
+    if chunks.is_empty() {
+        return "No sources were retrieved for this question.".to_string();
+    }
+
+    chunks
+        .iter()
+        .enumerate()
+        .map(|(index, chunk)| {
+            let line_range = match (chunk.start_line, chunk.end_line) {
+                (Some(start), Some(end)) => format!("#L{start}-L{end}"),
+                _ => String::new(),
+            };
+
+            format!(
+                "[{}] {}{}\n{}\n[/{}]",
+                index + 1,
+                chunk.source,
+                line_range,
+                chunk.content,
+                index + 1
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
@@ -0,0 +1,105 @@
+use crate::agents::answer::build_numbered_sources;
+use crate::budget::PromptBudget;
+use crate::config::GenerationParams;
+use crate::llm::LlmClient;
+use crate::models::{ChatMessage, ChatRole, RetrievedChunk, SessionMessage};
+use crate::prompts::PromptStore;
+use anyhow::Result;
+
+#[derive(Clone)]
+pub struct ChatAgent {
+    llm: LlmClient,
+    prompts: PromptStore,
+    params: GenerationParams,
+    budget: PromptBudget,
+    history_tokens: usize,
+}
+
+impl ChatAgent {
+    pub fn new(
+        llm: LlmClient,
+        prompts: PromptStore,
+        params: GenerationParams,
+        budget: PromptBudget,
+        history_tokens: usize,
+    ) -> Self {
+        Self {
+            llm,
+            prompts,
+            params,
+            budget,
+            history_tokens,
+        }
+    }
+
+    pub async fn run(
+        &self,
+        history: &[SessionMessage],
+        message: &str,
+        chunks: &[RetrievedChunk],
+    ) -> Result<String> {
+        let system = self
+            .prompts
+            .render("chat/chat", &[("sources", build_numbered_sources(chunks))])?;
+
+        // History gets what the system prompt, the new message and the reply
+        // leave of the context window, up to `history_tokens`.
+        let available = self
+            .budget
+            .available(&format!("{system}\n{message}"), self.params.max_tokens);
+        let history = truncate_history(&self.budget, history, self.history_tokens.min(available));
+
+        let mut messages = vec![ChatMessage {
+            role: ChatRole::System,
+            content: system,
+        }];
+
+        messages.extend(history.iter().map(|turn| ChatMessage {
+            role: turn.role,
+            content: turn.content.clone(),
+        }));
+
+        messages.push(ChatMessage {
+            role: ChatRole::User,
+            content: message.to_string(),
+        });
+
+        let answer = self
+            .llm
+            .chat_messages(
+                &messages,
+                self.params.temperature,
+                Some(self.params.max_tokens),
+            )
+            .await?;
+        Ok(answer.trim().to_string())
+    }
+}
+
+/// Keeps the most recent turns that fit in `max_tokens`, starting on a user
+/// turn so the model never sees a dangling reply.
+fn truncate_history<'a>(
+    budget: &PromptBudget,
+    history: &'a [SessionMessage],
+    max_tokens: usize,
+) -> &'a [SessionMessage] {
+    let mut used = 0;
+    let mut start = history.len();
+
+    for (index, turn) in history.iter().enumerate().rev() {
+        used += budget.count(&turn.content);
+        if used > max_tokens {
+            break;
+        }
+
+        start = index;
+    }
+
+    let kept = &history[start..];
+    let first_user = kept
+        .iter()
+        .position(|turn| turn.role == ChatRole::User)
+        .unwrap_or(kept.len());
+
+    &kept[first_user..]
+}
@@ -1,2 +1,6 @@
+pub mod answer;
+pub mod chat;
 pub mod planner;
+pub mod structure;
+pub mod workspace;
 pub mod writer;
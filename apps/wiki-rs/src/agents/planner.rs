@@ -1,35 +1,639 @@
+use super::structure::{Finding, Validation, validate};
+use super::workspace::{
+    MANIFEST_FILES, Package, SKIPPED_DIRS, detect_packages, fallback_plan, merge_plans, root_tree,
+};
+use crate::budget::PromptBudget;
+use crate::config::GenerationParams;
 use crate::llm::LlmClient;
-use crate::models::WikiStructure;
+use crate::models::{IssueResolution, PromptTrim, StructureIssue, WikiStructure};
 use crate::prompts::PromptStore;
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
+use futures::{StreamExt, stream};
+use std::collections::HashSet;
 use std::path::Path;
 use tokio::fs;
+use tracing::{info, warn};
+use walkdir::{DirEntry, WalkDir};
+
+/// Preferred README extensions, best first. The empty entry matches a bare `README`.
+const README_EXTENSIONS: [&str; 6] = ["md", "markdown", "rst", "adoc", "txt", ""];
+const ENTRY_POINT_NAMES: [&str; 12] = [
+    "main.rs",
+    "lib.rs",
+    "main.go",
+    "main.py",
+    "__main__.py",
+    "app.py",
+    "main.ts",
+    "index.ts",
+    "main.js",
+    "index.js",
+    "Main.java",
+    "main.cpp",
+];
+const MAX_DOC_FILES: usize = 3;
+const MAX_ENTRY_POINTS: usize = 4;
+const MAX_FALLBACK_FILE_CHARS: usize = 4_000;
+/// Share of the prompt the README may claim when it and the tree do not both fit.
+const README_SHARE_PERCENT: usize = 30;
+/// Below this many packages the repository is planned in a single call.
+const MIN_WORKSPACE_PACKAGES: usize = 2;
+const PACKAGE_PLAN_CONCURRENCY: usize = 3;
 
 #[derive(Clone)]
 pub struct PlannerAgent {
     llm: LlmClient,
     prompts: PromptStore,
+    params: GenerationParams,
+    budget: PromptBudget,
+}
+
+#[derive(Debug)]
+pub struct PlannerOutput {
+    pub structure: WikiStructure,
+    /// Files the planner read to understand the project, relative to its root.
+    pub inputs: Vec<String>,
+    pub trims: Vec<PromptTrim>,
+    pub issues: Vec<StructureIssue>,
+}
+
+struct ProjectOverview {
+    text: String,
+    inputs: Vec<String>,
 }
 
 impl PlannerAgent {
-    pub fn new(llm: LlmClient, prompts: PromptStore) -> Self {
-        Self { llm, prompts }
+    pub fn new(
+        llm: LlmClient,
+        prompts: PromptStore,
+        params: GenerationParams,
+        budget: PromptBudget,
+    ) -> Self {
+        Self {
+            llm,
+            prompts,
+            params,
+            budget,
+        }
+    }
+
+    /// Plans the wiki in one call, or, for a workspace with several packages,
+    /// plans the top level and then each package on its own.
+    pub async fn run(&self, project_path: &str, project_tree: &str) -> Result<PlannerOutput> {
+        let root = Path::new(project_path);
+        let packages = detect_packages(project_tree);
+        if packages.len() < MIN_WORKSPACE_PACKAGES {
+            let overview = project_overview(root).await?;
+            let output = self
+                .plan(
+                    "planner/planner",
+                    "planner",
+                    Vec::new(),
+                    project_tree,
+                    overview.text,
+                )
+                .await?;
+
+            return Ok(PlannerOutput {
+                inputs: overview.inputs,
+                ..output
+            });
+        }
+
+        info!(
+            packages = packages.len(),
+            "planning workspace package by package"
+        );
+
+        let overview = project_overview(root).await?;
+        let package_list = packages
+            .iter()
+            .map(|package| format!("- {}", package.path))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let root_plan = self
+            .plan(
+                "planner_root/planner_root",
+                "planner",
+                vec![("packages", package_list)],
+                &root_tree(project_tree, &packages),
+                overview.text,
+            )
+            .await?;
+        let mut inputs = overview.inputs;
+        let mut trims = root_plan.trims;
+        let mut issues = root_plan.issues;
+
+        let results = stream::iter(packages)
+            .map(|package| async move {
+                let result = self.plan_package(root, project_tree, &package).await;
+                (package, result)
+            })
+            .buffered(PACKAGE_PLAN_CONCURRENCY)
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut plans = Vec::new();
+        for (package, result) in results {
+            match result {
+                Ok(output) => {
+                    inputs.extend(output.inputs);
+                    trims.extend(output.trims);
+                    issues.extend(output.issues);
+                    plans.push((package, output.structure));
+                }
+                Err(error) => {
+                    warn!(
+                        package = %package.path,
+                        "package planning failed, using a single overview page: {error:#}"
+                    );
+                    let structure = fallback_plan(&package, project_tree);
+                    plans.push((package, structure));
+                }
+            }
+        }
+
+        // Each plan was validated alone; the merged one, including fallback
+        // plans, is checked against the whole tree.
+        let validation = validate(merge_plans(root_plan.structure, plans), project_tree);
+        issues.extend(structure_issues(
+            "planner:merged",
+            &validation.findings,
+            |finding| {
+                if finding.repaired {
+                    IssueResolution::Repaired
+                } else {
+                    IssueResolution::Unresolved
+                }
+            },
+        ));
+
+        Ok(PlannerOutput {
+            structure: validation.structure,
+            inputs,
+            trims,
+            issues,
+        })
+    }
+
+    async fn plan_package(
+        &self,
+        root: &Path,
+        project_tree: &str,
+        package: &Package,
+    ) -> Result<PlannerOutput> {
+        let overview = project_overview(&root.join(&package.path)).await?;
+        let output = self
+            .plan(
+                "planner_package/planner_package",
+                &format!("planner:{}", package.path),
+                vec![("package", package.path.clone())],
+                &package.tree(project_tree),
+                overview.text,
+            )
+            .await
+            .with_context(|| format!("failed to plan package {}", package.path))?;
+
+        Ok(PlannerOutput {
+            inputs: overview
+                .inputs
+                .into_iter()
+                .map(|input| format!("{}/{input}", package.path))
+                .collect(),
+            ..output
+        })
+    }
+
+    /// Fits the tree and README into the context window, asks for a
+    /// `WikiStructure` and validates it against `tree`. `label` names the
+    /// prompt in the reported trims and issues. `inputs` is left empty.
+    async fn plan(
+        &self,
+        prompt_key: &str,
+        label: &str,
+        vars: Vec<(&str, String)>,
+        tree: &str,
+        readme: String,
+    ) -> Result<PlannerOutput> {
+        let fixed = self.render(prompt_key, &vars, String::new(), String::new())?;
+        let available = self.budget.available(&fixed, self.params.max_tokens);
+        let (file_tree, readme, mut trims) = self.fit_inputs(label, available, tree, readme);
+        let prompt = self.render(prompt_key, &vars, file_tree.clone(), readme)?;
+
+        let structure = self
+            .llm
+            .chat_structured::<WikiStructure>(
+                &prompt,
+                "wiki_structure",
+                self.params.temperature,
+                Some(self.params.max_tokens),
+            )
+            .await
+            .with_context(|| "Planner output is not valid WikiStructure JSON")?;
+
+        let validation = validate(structure, tree);
+        let (structure, issues) = self
+            .repair(label, tree, &file_tree, validation, &mut trims)
+            .await;
+        if structure.pages.is_empty() {
+            bail!("Planner produced a structure without pages");
+        }
+
+        Ok(PlannerOutput {
+            structure,
+            inputs: Vec::new(),
+            trims,
+            issues,
+        })
     }
 
-    pub async fn run(&self, project_path: &str, project_tree: &str) -> Result<WikiStructure> {
-        let readme_path = Path::new(project_path).join("README.md");
-        let readme = fs::read_to_string(&readme_path)
+    /// Sends what `validate` could not repair back to the LLM once, then
+    /// validates its answer again. When the fix-up fails the repaired
+    /// structure is kept and the remaining issues are reported unresolved.
+    async fn repair(
+        &self,
+        label: &str,
+        tree: &str,
+        file_tree: &str,
+        validation: Validation,
+        trims: &mut Vec<PromptTrim>,
+    ) -> (WikiStructure, Vec<StructureIssue>) {
+        let pending = validation.unrepaired();
+        if pending.is_empty() {
+            let issues =
+                structure_issues(label, &validation.findings, |_| IssueResolution::Repaired);
+            return (validation.structure, issues);
+        }
+
+        let fixed = match self
+            .fix(label, &validation.structure, &pending, file_tree, trims)
             .await
-            .with_context(|| format!("Failed to read {}", readme_path.display()))?;
+        {
+            Ok(fixed) => fixed,
+            Err(error) => {
+                warn!(prompt = label, "planner fix-up failed: {error:#}");
+                let issues = structure_issues(label, &validation.findings, |finding| {
+                    if finding.repaired {
+                        IssueResolution::Repaired
+                    } else {
+                        IssueResolution::Unresolved
+                    }
+                });
+                return (validation.structure, issues);
+            }
+        };
+
+        let revalidated = validate(fixed, tree);
+        let before = pending
+            .iter()
+            .map(|finding| (finding.kind, finding.id.as_str()))
+            .collect::<HashSet<_>>();
+        let after = revalidated
+            .unrepaired()
+            .into_iter()
+            .map(|finding| (finding.kind, finding.id.as_str()))
+            .collect::<HashSet<_>>();
 
-        let prompt = self.prompts.render(
-            "planner/planner",
-            &[("fileTree", project_tree.to_string()), ("readme", readme)],
-        )?;
+        let mut issues = structure_issues(label, &validation.findings, |finding| {
+            if finding.repaired {
+                IssueResolution::Repaired
+            } else if after.contains(&(finding.kind, finding.id.as_str())) {
+                IssueResolution::Unresolved
+            } else {
+                IssueResolution::FixedByLlm
+            }
+        });
+        let new_findings = revalidated
+            .findings
+            .iter()
+            .filter(|finding| {
+                finding.repaired || !before.contains(&(finding.kind, finding.id.as_str()))
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        issues.extend(structure_issues(label, &new_findings, |finding| {
+            if finding.repaired {
+                IssueResolution::Repaired
+            } else {
+                IssueResolution::Unresolved
+            }
+        }));
+
+        (revalidated.structure, issues)
+    }
+
+    async fn fix(
+        &self,
+        label: &str,
+        structure: &WikiStructure,
+        pending: &[&Finding],
+        file_tree: &str,
+        trims: &mut Vec<PromptTrim>,
+    ) -> Result<WikiStructure> {
+        let structure = serde_json::to_string_pretty(structure)?;
+        let issues = pending
+            .iter()
+            .map(|finding| format!("- {}", finding.detail))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let render = |file_tree: String| {
+            self.prompts.render(
+                "planner_fix/planner_fix",
+                &[
+                    ("fileTree", file_tree),
+                    ("structure", structure.clone()),
+                    ("issues", issues.clone()),
+                ],
+            )
+        };
+
+        let available = self
+            .budget
+            .available(&render(String::new())?, self.params.max_tokens);
+        let original_tokens = self.budget.count(file_tree);
+        let (file_tree, depth) = self.budget.collapse_tree(file_tree, available);
+        if let Some(depth) = depth {
+            trims.push(PromptTrim {
+                prompt: format!("{label}:fix"),
+                page_id: None,
+                section: "fileTree".to_string(),
+                original_tokens,
+                kept_tokens: self.budget.count(&file_tree),
+                detail: format!("directories below depth {depth} collapsed into summaries"),
+            });
+        }
 
         self.llm
-            .chat_structured::<WikiStructure>(&prompt, "wiki_structure", 0.0, Some(3000))
+            .chat_structured::<WikiStructure>(
+                &render(file_tree)?,
+                "wiki_structure",
+                self.params.temperature,
+                Some(self.params.max_tokens),
+            )
+            .await
+            .with_context(|| "Planner fix-up output is not valid WikiStructure JSON")
+    }
+
+    fn render(
+        &self,
+        prompt_key: &str,
+        vars: &[(&str, String)],
+        file_tree: String,
+        readme: String,
+    ) -> Result<String> {
+        let mut vars = vars.to_vec();
+        vars.push(("fileTree", file_tree));
+        vars.push(("readme", readme));
+        self.prompts.render(prompt_key, &vars)
+    }
+
+    /// Splits `available` tokens between the tree and the README. The README
+    /// keeps at least its share; the tree is collapsed by directory before it
+    /// is cut.
+    fn fit_inputs(
+        &self,
+        label: &str,
+        available: usize,
+        tree: &str,
+        readme: String,
+    ) -> (String, String, Vec<PromptTrim>) {
+        let tree_tokens = self.budget.count(tree);
+        let readme_tokens = self.budget.count(&readme);
+        if tree_tokens + readme_tokens <= available {
+            return (tree.to_string(), readme, Vec::new());
+        }
+
+        let readme_budget = readme_tokens.min(
+            available
+                .saturating_sub(tree_tokens)
+                .max(available * README_SHARE_PERCENT / 100),
+        );
+        let tree_budget = available - readme_budget;
+        let mut trims = Vec::new();
+
+        let readme = if readme_tokens > readme_budget {
+            let truncated = self.budget.truncate_lines(&readme, readme_budget);
+            trims.push(PromptTrim {
+                prompt: label.to_string(),
+                page_id: None,
+                section: "readme".to_string(),
+                original_tokens: readme_tokens,
+                kept_tokens: self.budget.count(&truncated),
+                detail: "truncated at the end".to_string(),
+            });
+            truncated
+        } else {
+            readme
+        };
+
+        let (tree, depth) = self.budget.collapse_tree(tree, tree_budget);
+        if let Some(depth) = depth {
+            trims.push(PromptTrim {
+                prompt: label.to_string(),
+                page_id: None,
+                section: "fileTree".to_string(),
+                original_tokens: tree_tokens,
+                kept_tokens: self.budget.count(&tree),
+                detail: format!("directories below depth {depth} collapsed into summaries"),
+            });
+        }
+
+        (tree, readme, trims)
+    }
+}
+
+fn structure_issues(
+    label: &str,
+    findings: &[Finding],
+    resolution: impl Fn(&Finding) -> IssueResolution,
+) -> Vec<StructureIssue> {
+    findings
+        .iter()
+        .map(|finding| StructureIssue {
+            prompt: label.to_string(),
+            kind: finding.kind,
+            id: finding.id.clone(),
+            detail: finding.detail.clone(),
+            resolution: resolution(finding),
+        })
+        .collect()
+}
+
+/// The root README when there is one; otherwise an overview assembled from
+/// `docs/`, package manifests and a sample of entry points.
+async fn project_overview(root: &Path) -> Result<ProjectOverview> {
+    if let Some(readme) = find_readme(root).await? {
+        let raw = fs::read(root.join(&readme))
+            .await
+            .with_context(|| format!("Failed to read {readme}"))?;
+
+        return Ok(ProjectOverview {
+            text: String::from_utf8_lossy(&raw).into_owned(),
+            inputs: vec![readme],
+        });
+    }
+
+    let mut parts = Vec::new();
+    let mut inputs = Vec::new();
+    for relative_path in fallback_inputs(root).await? {
+        let Ok(raw) = fs::read(root.join(&relative_path)).await else {
+            continue;
+        };
+
+        let content = String::from_utf8_lossy(&raw);
+        parts.push(format!(
+            "### {relative_path}\n```\n{}\n```",
+            truncate(&content, MAX_FALLBACK_FILE_CHARS)
+        ));
+        inputs.push(relative_path);
+    }
+
+    let text = if parts.is_empty() {
+        "The repository has no README, docs, manifests or recognizable entry points. \
+         Rely on the file tree."
+            .to_string()
+    } else {
+        format!(
+            "The repository has no README. This overview was assembled from the files below.\n\n{}",
+            parts.join("\n\n")
+        )
+    };
+
+    Ok(ProjectOverview { text, inputs })
+}
+
+/// Finds `README`, `readme.md`, `Readme.rst`, ... in `dir`, ignoring case.
+async fn find_readme(dir: &Path) -> Result<Option<String>> {
+    let mut entries = match fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(error.into()),
+    };
+
+    let mut best: Option<(usize, String)> = None;
+    while let Some(entry) = entries.next_entry().await? {
+        if !entry.file_type().await?.is_file() {
+            continue;
+        }
+
+        let name = entry.file_name().to_string_lossy().to_string();
+        let lowercase = name.to_lowercase();
+        let (stem, extension) = lowercase.split_once('.').unwrap_or((&lowercase, ""));
+        if stem != "readme" {
+            continue;
+        }
+
+        let Some(rank) = README_EXTENSIONS.iter().position(|item| *item == extension) else {
+            continue;
+        };
+
+        if best
+            .as_ref()
+            .is_none_or(|(best_rank, best_name)| (rank, &name) < (*best_rank, best_name))
+        {
+            best = Some((rank, name));
+        }
+    }
+
+    Ok(best.map(|(_, name)| name))
+}
+
+async fn fallback_inputs(root: &Path) -> Result<Vec<String>> {
+    let mut inputs = docs_files(root).await?;
+
+    for manifest in MANIFEST_FILES {
+        if fs::metadata(root.join(manifest))
             .await
-            .with_context(|| "Planner output is not valid WikiStructure JSON")
+            .is_ok_and(|metadata| metadata.is_file())
+        {
+            inputs.push(manifest.to_string());
+        }
+    }
+
+    inputs.extend(sample_entry_points(root));
+    Ok(inputs)
+}
+
+/// The README of a top-level `docs/` directory, or its first markdown files.
+async fn docs_files(root: &Path) -> Result<Vec<String>> {
+    let mut entries = fs::read_dir(root).await?;
+    let mut docs_dir = None;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if matches!(name.to_lowercase().as_str(), "docs" | "doc")
+            && entry.file_type().await?.is_dir()
+        {
+            docs_dir = Some(name);
+            break;
+        }
+    }
+
+    let Some(docs_dir) = docs_dir else {
+        return Ok(Vec::new());
+    };
+
+    if let Some(readme) = find_readme(&root.join(&docs_dir)).await? {
+        return Ok(vec![format!("{docs_dir}/{readme}")]);
+    }
+
+    let mut markdown_files = Vec::new();
+    let mut entries = fs::read_dir(root.join(&docs_dir)).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.to_lowercase().ends_with(".md") && entry.file_type().await?.is_file() {
+            markdown_files.push(name);
+        }
+    }
+
+    // `index.md` usually introduces the rest, so it goes first.
+    markdown_files.sort_by_key(|name| (name.to_lowercase() != "index.md", name.clone()));
+
+    Ok(markdown_files
+        .into_iter()
+        .take(MAX_DOC_FILES)
+        .map(|name| format!("{docs_dir}/{name}"))
+        .collect())
+}
+
+/// Shallowest entry-point files first, skipping dependency and build directories.
+fn sample_entry_points(root: &Path) -> Vec<String> {
+    let mut entry_points = WalkDir::new(root)
+        .max_depth(4)
+        .into_iter()
+        .filter_entry(|entry| entry.depth() == 0 || !is_skipped_dir(entry))
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter(|entry| {
+            ENTRY_POINT_NAMES
+                .iter()
+                .any(|name| entry.file_name() == *name)
+        })
+        .filter_map(|entry| {
+            let relative = entry.path().strip_prefix(root).ok()?;
+            Some((entry.depth(), relative.to_string_lossy().replace('\\', "/")))
+        })
+        .collect::<Vec<_>>();
+
+    entry_points.sort();
+    entry_points
+        .into_iter()
+        .take(MAX_ENTRY_POINTS)
+        .map(|(_, path)| path)
+        .collect()
+}
+
+fn is_skipped_dir(entry: &DirEntry) -> bool {
+    if !entry.file_type().is_dir() {
+        return false;
+    }
+
+    let name = entry.file_name().to_string_lossy();
+    name.starts_with('.') || SKIPPED_DIRS.contains(&name.as_ref())
+}
+
+fn truncate(content: &str, max_chars: usize) -> String {
+    match content.char_indices().nth(max_chars) {
+        Some((index, _)) => format!("{}\n[...]", &content[..index]),
+        None => content.to_string(),
     }
 }
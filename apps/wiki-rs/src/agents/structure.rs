@@ -0,0 +1,739 @@
+use crate::models::{StructureIssueKind, WikiSection, WikiStructure};
+use std::collections::{HashMap, HashSet};
+
+/// Takes the pages whose parent section cannot be found.
+const FALLBACK_SECTION_ID: &str = "general";
+const FALLBACK_SECTION_TITLE: &str = "General";
+
+#[derive(Clone, Debug)]
+pub struct Finding {
+    pub kind: StructureIssueKind,
+    pub id: String,
+    pub detail: String,
+    /// Unrepaired findings are left for the LLM to fix.
+    pub repaired: bool,
+}
+
+pub struct Validation {
+    pub structure: WikiStructure,
+    pub findings: Vec<Finding>,
+}
+
+impl Validation {
+    pub fn unrepaired(&self) -> Vec<&Finding> {
+        self.findings
+            .iter()
+            .filter(|finding| !finding.repaired)
+            .collect()
+    }
+}
+
+/// Checks the ids and references of a planned structure against each other
+/// and against `repo_tree`, repairing whatever has an obvious fix: unknown
+/// files and references are dropped, duplicated ids renamed and pages no
+/// section lists re-parented.
+pub fn validate(structure: WikiStructure, repo_tree: &str) -> Validation {
+    let tree = repo_tree
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<HashSet<_>>();
+
+    let mut validation = Validation {
+        structure,
+        findings: Vec::new(),
+    };
+    validation.dedupe_page_ids();
+    validation.dedupe_section_ids();
+    validation.check_files(&tree);
+    validation.check_section_pages();
+    validation.check_subsections();
+    validation.place_pages();
+    validation.check_related_pages();
+    validation.drop_empty_sections();
+
+    if validation.structure.pages.is_empty() {
+        validation.unresolved(
+            StructureIssueKind::NoPages,
+            "",
+            "the structure has no pages".to_string(),
+        );
+    }
+
+    validation
+}
+
+impl Validation {
+    fn repaired(&mut self, kind: StructureIssueKind, id: &str, detail: String) {
+        self.findings.push(Finding {
+            kind,
+            id: id.to_string(),
+            detail,
+            repaired: true,
+        });
+    }
+
+    fn unresolved(&mut self, kind: StructureIssueKind, id: &str, detail: String) {
+        self.findings.push(Finding {
+            kind,
+            id: id.to_string(),
+            detail,
+            repaired: false,
+        });
+    }
+
+    /// Later pages with an id already in use are renamed; references keep
+    /// pointing at the first one.
+    fn dedupe_page_ids(&mut self) {
+        let mut taken = self
+            .structure
+            .pages
+            .iter()
+            .map(|page| page.id.clone())
+            .collect::<HashSet<_>>();
+        let mut seen = HashSet::new();
+        let mut renamed = Vec::new();
+
+        for page in &mut self.structure.pages {
+            if seen.insert(page.id.clone()) {
+                continue;
+            }
+
+            let id = unique_id(&page.id, &taken);
+            taken.insert(id.clone());
+            renamed.push((page.id.clone(), id.clone()));
+            page.id = id;
+        }
+
+        for (old, new) in renamed {
+            self.repaired(
+                StructureIssueKind::DuplicatePageId,
+                &old,
+                format!(
+                    "page id `{old}` is used more than once; the repeat was renamed to `{new}`"
+                ),
+            );
+        }
+    }
+
+    fn dedupe_section_ids(&mut self) {
+        let mut taken = self
+            .structure
+            .sections
+            .iter()
+            .map(|section| section.id.clone())
+            .collect::<HashSet<_>>();
+        let mut seen = HashSet::new();
+        let mut renamed = Vec::new();
+
+        for section in &mut self.structure.sections {
+            if seen.insert(section.id.clone()) {
+                continue;
+            }
+
+            let id = unique_id(&section.id, &taken);
+            taken.insert(id.clone());
+            renamed.push((section.id.clone(), id.clone()));
+            section.id = id;
+        }
+
+        for (old, new) in renamed {
+            self.repaired(
+                StructureIssueKind::DuplicateSectionId,
+                &old,
+                format!(
+                    "section id `{old}` is used more than once; the repeat was renamed to `{new}`"
+                ),
+            );
+        }
+    }
+
+    fn check_files(&mut self, tree: &HashSet<&str>) {
+        let mut findings = Vec::new();
+
+        for page in &mut self.structure.pages {
+            if page.relevant_files.is_empty() {
+                continue;
+            }
+
+            let mut kept: Vec<String> = Vec::new();
+            let mut dropped = Vec::new();
+            for file in page.relevant_files.drain(..) {
+                let normalized = file.trim().trim_start_matches("./");
+                if !tree.contains(normalized) {
+                    dropped.push(file);
+                } else if !kept.iter().any(|kept| kept == normalized) {
+                    kept.push(normalized.to_string());
+                }
+            }
+
+            if !dropped.is_empty() {
+                findings.push((
+                    StructureIssueKind::UnknownFile,
+                    page.id.clone(),
+                    format!(
+                        "page `{}` lists files that are not in the tree: {}",
+                        page.id,
+                        dropped.join(", ")
+                    ),
+                ));
+            }
+
+            if kept.is_empty() {
+                findings.push((
+                    StructureIssueKind::PageWithoutFiles,
+                    page.id.clone(),
+                    format!(
+                        "none of the relevant_files of page `{}` are in the tree; pick existing files for it",
+                        page.id
+                    ),
+                ));
+            }
+
+            page.relevant_files = kept;
+        }
+
+        for (kind, id, detail) in findings {
+            match kind {
+                StructureIssueKind::PageWithoutFiles => self.unresolved(kind, &id, detail),
+                _ => self.repaired(kind, &id, detail),
+            }
+        }
+    }
+
+    /// Drops unknown pages from sections and keeps each page in a single
+    /// section: its parent when that lists it, otherwise the first that does.
+    fn check_section_pages(&mut self) {
+        let page_ids = self
+            .structure
+            .pages
+            .iter()
+            .map(|page| page.id.clone())
+            .collect::<HashSet<_>>();
+        let parents = self
+            .structure
+            .pages
+            .iter()
+            .map(|page| (page.id.clone(), page.parent_section.clone()))
+            .collect::<HashMap<_, _>>();
+        let mut findings = Vec::new();
+
+        for section in &mut self.structure.sections {
+            let mut kept = Vec::new();
+            for page_id in std::mem::take(&mut section.pages) {
+                if !page_ids.contains(&page_id) {
+                    findings.push((
+                        StructureIssueKind::UnknownPage,
+                        section.id.clone(),
+                        format!(
+                            "section `{}` lists page `{page_id}`, which does not exist",
+                            section.id
+                        ),
+                    ));
+                } else if !kept.contains(&page_id) {
+                    kept.push(page_id);
+                }
+            }
+            section.pages = kept;
+        }
+
+        let mut home: HashMap<String, String> = HashMap::new();
+        for section in &self.structure.sections {
+            for page_id in &section.pages {
+                let is_parent = parents
+                    .get(page_id)
+                    .is_some_and(|parent| parent.as_deref() == Some(section.id.as_str()));
+                if is_parent || !home.contains_key(page_id) {
+                    home.insert(page_id.clone(), section.id.clone());
+                }
+            }
+        }
+
+        for section in &mut self.structure.sections {
+            section.pages.retain(|page_id| {
+                let kept_in = &home[page_id];
+                if *kept_in == section.id {
+                    return true;
+                }
+
+                findings.push((
+                    StructureIssueKind::RepeatedPage,
+                    page_id.clone(),
+                    format!(
+                        "page `{page_id}` is listed in several sections; it was removed from `{}` and kept in `{kept_in}`",
+                        section.id
+                    ),
+                ));
+                false
+            });
+        }
+
+        for (kind, id, detail) in findings {
+            self.repaired(kind, &id, detail);
+        }
+    }
+
+    /// Drops subsection links to unknown sections and links that would give
+    /// a section a second parent or close a cycle.
+    fn check_subsections(&mut self) {
+        let section_ids = self
+            .structure
+            .sections
+            .iter()
+            .map(|section| section.id.clone())
+            .collect::<HashSet<_>>();
+        let mut parent_of: HashMap<String, String> = HashMap::new();
+        let mut findings = Vec::new();
+
+        for index in 0..self.structure.sections.len() {
+            let section_id = self.structure.sections[index].id.clone();
+            let mut kept = Vec::new();
+
+            for child in std::mem::take(&mut self.structure.sections[index].subsections) {
+                let problem = if !section_ids.contains(&child) {
+                    Some("does not exist".to_string())
+                } else if child == section_id {
+                    Some("is the section itself".to_string())
+                } else if let Some(parent) = parent_of.get(&child) {
+                    Some(format!("is already a subsection of `{parent}`"))
+                } else if is_ancestor(&parent_of, &child, &section_id) {
+                    Some("would create a cycle".to_string())
+                } else {
+                    None
+                };
+
+                match problem {
+                    Some(problem) => findings.push((
+                        section_id.clone(),
+                        format!("subsection `{child}` of section `{section_id}` {problem}"),
+                    )),
+                    None => {
+                        parent_of.insert(child.clone(), section_id.clone());
+                        kept.push(child);
+                    }
+                }
+            }
+
+            self.structure.sections[index].subsections = kept;
+        }
+
+        for (id, detail) in findings {
+            self.repaired(StructureIssueKind::InvalidSubsection, &id, detail);
+        }
+    }
+
+    /// Points every page at the section that lists it. Pages no section
+    /// lists join their parent section, or the fallback section when the
+    /// parent does not exist.
+    fn place_pages(&mut self) {
+        let section_ids = self
+            .structure
+            .sections
+            .iter()
+            .map(|section| section.id.clone())
+            .collect::<HashSet<_>>();
+        let listed = self
+            .structure
+            .sections
+            .iter()
+            .flat_map(|section| {
+                section
+                    .pages
+                    .iter()
+                    .map(|page_id| (page_id.clone(), section.id.clone()))
+            })
+            .collect::<HashMap<_, _>>();
+        let mut additions = Vec::new();
+        let mut findings = Vec::new();
+
+        for page in &mut self.structure.pages {
+            let parent = page.parent_section.clone();
+            let dangling = parent
+                .as_ref()
+                .filter(|parent| !section_ids.contains(*parent));
+
+            match listed.get(&page.id) {
+                Some(section) if parent.as_ref() == Some(section) => {}
+                Some(section) => {
+                    let (kind, detail) = match dangling {
+                        Some(parent) => (
+                            StructureIssueKind::DanglingParent,
+                            format!(
+                                "parent section `{parent}` of page `{}` does not exist; re-parented to `{section}`",
+                                page.id
+                            ),
+                        ),
+                        None => (
+                            StructureIssueKind::ParentMismatch,
+                            format!(
+                                "page `{}` is listed in section `{section}` but names `{}` as its parent",
+                                page.id,
+                                parent.as_deref().unwrap_or("none")
+                            ),
+                        ),
+                    };
+                    findings.push((kind, page.id.clone(), detail));
+                    page.parent_section = Some(section.clone());
+                }
+                None => {
+                    let target = match parent.clone().filter(|parent| section_ids.contains(parent))
+                    {
+                        Some(parent) => parent,
+                        None => {
+                            if let Some(parent) = dangling {
+                                findings.push((
+                                    StructureIssueKind::DanglingParent,
+                                    page.id.clone(),
+                                    format!(
+                                        "parent section `{parent}` of page `{}` does not exist",
+                                        page.id
+                                    ),
+                                ));
+                            }
+                            FALLBACK_SECTION_ID.to_string()
+                        }
+                    };
+
+                    findings.push((
+                        StructureIssueKind::OrphanPage,
+                        page.id.clone(),
+                        format!(
+                            "page `{}` is not listed by any section; it was added to `{target}`",
+                            page.id
+                        ),
+                    ));
+                    page.parent_section = Some(target.clone());
+                    additions.push((target, page.id.clone()));
+                }
+            }
+        }
+
+        for (section_id, page_id) in additions {
+            let index = match self
+                .structure
+                .sections
+                .iter()
+                .position(|section| section.id == section_id)
+            {
+                Some(index) => index,
+                None => {
+                    self.structure.sections.push(WikiSection {
+                        id: FALLBACK_SECTION_ID.to_string(),
+                        title: FALLBACK_SECTION_TITLE.to_string(),
+                        pages: Vec::new(),
+                        subsections: Vec::new(),
+                    });
+                    self.structure.sections.len() - 1
+                }
+            };
+            self.structure.sections[index].pages.push(page_id);
+        }
+
+        for (kind, id, detail) in findings {
+            self.repaired(kind, &id, detail);
+        }
+    }
+
+    fn check_related_pages(&mut self) {
+        let page_ids = self
+            .structure
+            .pages
+            .iter()
+            .map(|page| page.id.clone())
+            .collect::<HashSet<_>>();
+        let mut findings = Vec::new();
+
+        for page in &mut self.structure.pages {
+            let mut kept: Vec<String> = Vec::new();
+            let mut dropped = Vec::new();
+            for related in page.related_pages.drain(..) {
+                if related == page.id || !page_ids.contains(&related) {
+                    dropped.push(related);
+                } else if !kept.contains(&related) {
+                    kept.push(related);
+                }
+            }
+
+            if !dropped.is_empty() {
+                findings.push((
+                    page.id.clone(),
+                    format!(
+                        "page `{}` relates to pages that do not exist or to itself: {}",
+                        page.id,
+                        dropped.join(", ")
+                    ),
+                ));
+            }
+            page.related_pages = kept;
+        }
+
+        for (id, detail) in findings {
+            self.repaired(StructureIssueKind::UnknownRelatedPage, &id, detail);
+        }
+    }
+
+    /// Removes sections with neither pages nor subsections, repeatedly, since
+    /// removing one can empty its parent.
+    fn drop_empty_sections(&mut self) {
+        loop {
+            let empty = self
+                .structure
+                .sections
+                .iter()
+                .filter(|section| section.pages.is_empty() && section.subsections.is_empty())
+                .map(|section| section.id.clone())
+                .collect::<Vec<_>>();
+            if empty.is_empty() {
+                return;
+            }
+
+            self.structure
+                .sections
+                .retain(|section| !empty.contains(&section.id));
+            for section in &mut self.structure.sections {
+                section.subsections.retain(|id| !empty.contains(id));
+            }
+
+            for id in empty {
+                self.repaired(
+                    StructureIssueKind::EmptySection,
+                    &id,
+                    format!("section `{id}` has no pages or subsections and was removed"),
+                );
+            }
+        }
+    }
+}
+
+fn unique_id(id: &str, taken: &HashSet<String>) -> String {
+    let mut suffix = 2;
+    loop {
+        let candidate = format!("{id}-{suffix}");
+        if !taken.contains(&candidate) {
+            return candidate;
+        }
+        suffix += 1;
+    }
+}
+
+/// Whether `candidate` is `section` or one of its ancestors.
+fn is_ancestor(parent_of: &HashMap<String, String>, candidate: &str, section: &str) -> bool {
+    let mut current = Some(section);
+    while let Some(id) = current {
+        if id == candidate {
+            return true;
+        }
+        current = parent_of.get(id).map(String::as_str);
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Importance, WikiPage};
+
+    const TREE: &str = "src/main.rs\nsrc/lib.rs\n";
+
+    fn page(id: &str, parent: Option<&str>) -> WikiPage {
+        WikiPage {
+            id: id.to_string(),
+            title: id.to_string(),
+            description: String::new(),
+            importance: Importance::Medium,
+            relevant_files: vec!["src/main.rs".to_string()],
+            related_pages: Vec::new(),
+            parent_section: parent.map(str::to_string),
+        }
+    }
+
+    fn section(id: &str, pages: &[&str], subsections: &[&str]) -> WikiSection {
+        WikiSection {
+            id: id.to_string(),
+            title: id.to_string(),
+            pages: pages.iter().map(|page| page.to_string()).collect(),
+            subsections: subsections.iter().map(|id| id.to_string()).collect(),
+        }
+    }
+
+    fn structure(sections: Vec<WikiSection>, pages: Vec<WikiPage>) -> WikiStructure {
+        WikiStructure {
+            title: "Wiki".to_string(),
+            description: String::new(),
+            sections,
+            pages,
+        }
+    }
+
+    fn kinds(validation: &Validation) -> Vec<StructureIssueKind> {
+        validation
+            .findings
+            .iter()
+            .map(|finding| finding.kind)
+            .collect()
+    }
+
+    fn find<'a>(validation: &'a Validation, id: &str) -> &'a WikiSection {
+        validation
+            .structure
+            .sections
+            .iter()
+            .find(|section| section.id == id)
+            .unwrap()
+    }
+
+    #[test]
+    fn subsection_cycles_are_broken() {
+        let validation = validate(
+            structure(
+                vec![
+                    section("a", &["one"], &["b"]),
+                    section("b", &["two"], &["a"]),
+                    section("c", &["three"], &["c"]),
+                ],
+                vec![
+                    page("one", Some("a")),
+                    page("two", Some("b")),
+                    page("three", Some("c")),
+                ],
+            ),
+            TREE,
+        );
+
+        assert_eq!(find(&validation, "a").subsections, vec!["b"]);
+        assert!(find(&validation, "b").subsections.is_empty());
+        assert!(find(&validation, "c").subsections.is_empty());
+        assert_eq!(
+            kinds(&validation),
+            vec![
+                StructureIssueKind::InvalidSubsection,
+                StructureIssueKind::InvalidSubsection
+            ]
+        );
+        assert!(validation.unrepaired().is_empty());
+    }
+
+    #[test]
+    fn orphan_pages_join_their_parent_or_the_fallback_section() {
+        let validation = validate(
+            structure(
+                vec![section("a", &["one"], &[])],
+                vec![
+                    page("one", Some("a")),
+                    page("two", Some("a")),
+                    page("three", Some("missing")),
+                ],
+            ),
+            TREE,
+        );
+
+        assert_eq!(find(&validation, "a").pages, vec!["one", "two"]);
+        assert_eq!(find(&validation, FALLBACK_SECTION_ID).pages, vec!["three"]);
+        let three = &validation.structure.pages[2];
+        assert_eq!(three.parent_section.as_deref(), Some(FALLBACK_SECTION_ID));
+        assert_eq!(
+            kinds(&validation),
+            vec![
+                StructureIssueKind::OrphanPage,
+                StructureIssueKind::DanglingParent,
+                StructureIssueKind::OrphanPage,
+            ]
+        );
+    }
+
+    #[test]
+    fn pages_listed_twice_stay_in_their_parent() {
+        let validation = validate(
+            structure(
+                vec![
+                    section("a", &["one"], &[]),
+                    section("b", &["one", "two"], &[]),
+                ],
+                vec![page("one", Some("b")), page("two", Some("b"))],
+            ),
+            TREE,
+        );
+
+        assert_eq!(find(&validation, "b").pages, vec!["one", "two"]);
+        assert!(
+            validation
+                .structure
+                .sections
+                .iter()
+                .all(|section| section.id != "a")
+        );
+        assert_eq!(
+            kinds(&validation),
+            vec![
+                StructureIssueKind::RepeatedPage,
+                StructureIssueKind::EmptySection
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicate_ids_are_renamed() {
+        let validation = validate(
+            structure(
+                vec![
+                    section("a", &["intro"], &[]),
+                    section("a", &["intro-2"], &[]),
+                ],
+                vec![page("intro", Some("a")), page("intro", Some("a-2"))],
+            ),
+            TREE,
+        );
+
+        let page_ids = validation
+            .structure
+            .pages
+            .iter()
+            .map(|page| page.id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(page_ids, vec!["intro", "intro-2"]);
+        assert_eq!(find(&validation, "a-2").pages, vec!["intro-2"]);
+        assert_eq!(
+            kinds(&validation),
+            vec![
+                StructureIssueKind::DuplicatePageId,
+                StructureIssueKind::DuplicateSectionId
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_files_are_dropped_and_pages_left_without_any_are_unresolved() {
+        let mut known = page("known", Some("a"));
+        known.relevant_files = vec!["./src/lib.rs".to_string(), "src/gone.rs".to_string()];
+        let mut unknown = page("unknown", Some("a"));
+        unknown.relevant_files = vec!["src/gone.rs".to_string()];
+
+        let validation = validate(
+            structure(
+                vec![section("a", &["known", "unknown"], &[])],
+                vec![known, unknown],
+            ),
+            TREE,
+        );
+
+        assert_eq!(
+            validation.structure.pages[0].relevant_files,
+            vec!["src/lib.rs"]
+        );
+        let unrepaired = validation.unrepaired();
+        assert_eq!(unrepaired.len(), 1);
+        assert_eq!(unrepaired[0].kind, StructureIssueKind::PageWithoutFiles);
+        assert_eq!(unrepaired[0].id, "unknown");
+    }
+
+    #[test]
+    fn a_structure_without_pages_is_unresolved() {
+        let validation = validate(structure(vec![section("a", &[], &[])], Vec::new()), TREE);
+
+        assert!(validation.structure.sections.is_empty());
+        let unrepaired = validation.unrepaired();
+        assert_eq!(unrepaired.len(), 1);
+        assert_eq!(unrepaired[0].kind, StructureIssueKind::NoPages);
+    }
+}
@@ -0,0 +1,284 @@
+use crate::models::{Importance, WikiPage, WikiSection, WikiStructure};
+use std::collections::{BTreeMap, HashSet};
+
+pub const MANIFEST_FILES: [&str; 4] = ["Cargo.toml", "package.json", "pyproject.toml", "go.mod"];
+pub const SKIPPED_DIRS: [&str; 7] = [
+    "node_modules",
+    "target",
+    "dist",
+    "build",
+    "vendor",
+    "venv",
+    "__pycache__",
+];
+/// Manifests under these directories belong to samples rather than to
+/// packages of the workspace.
+const NON_PACKAGE_DIRS: [&str; 5] = ["examples", "fixtures", "testdata", "tests", "test"];
+
+/// A directory of the repository with its own package manifest.
+#[derive(Clone, Debug)]
+pub struct Package {
+    /// Relative to the repository root, without a trailing slash.
+    pub path: String,
+}
+
+impl Package {
+    /// Keeps the ids of this package unique once its plan is merged.
+    pub fn id_prefix(&self) -> String {
+        self.path
+            .chars()
+            .map(|character| {
+                if character.is_ascii_alphanumeric() {
+                    character.to_ascii_lowercase()
+                } else {
+                    '-'
+                }
+            })
+            .collect()
+    }
+
+    /// The lines of `repo_tree` inside this package.
+    pub fn tree(&self, repo_tree: &str) -> String {
+        repo_tree
+            .lines()
+            .map(str::trim)
+            .filter(|line| self.contains(line))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn contains(&self, path: &str) -> bool {
+        path.strip_prefix(&self.path)
+            .is_some_and(|rest| rest.starts_with('/'))
+    }
+}
+
+/// Outermost directories below the root holding a manifest, e.g. the
+/// members of a Cargo or npm workspace. Packages nested in another package
+/// are planned as part of it.
+pub fn detect_packages(repo_tree: &str) -> Vec<Package> {
+    let mut dirs = repo_tree
+        .lines()
+        .map(str::trim)
+        .filter_map(|path| {
+            let (dir, file_name) = path.rsplit_once('/')?;
+            if !MANIFEST_FILES.contains(&file_name) {
+                return None;
+            }
+
+            let skipped = dir.split('/').any(|component| {
+                component.starts_with('.')
+                    || SKIPPED_DIRS.contains(&component)
+                    || NON_PACKAGE_DIRS.contains(&component)
+            });
+            (!skipped).then(|| dir.to_string())
+        })
+        .collect::<Vec<_>>();
+
+    // Parents sort before their children, so the first match wins.
+    dirs.sort();
+    dirs.dedup();
+
+    let mut packages: Vec<Package> = Vec::new();
+    for dir in dirs {
+        if packages.iter().any(|package| package.contains(&dir)) {
+            continue;
+        }
+
+        packages.push(Package { path: dir });
+    }
+
+    packages
+}
+
+/// The repository tree with each package folded into its manifests and a
+/// one-line summary, for the top-level plan.
+pub fn root_tree(repo_tree: &str, packages: &[Package]) -> String {
+    let mut lines = BTreeMap::new();
+    let mut package_files = vec![0usize; packages.len()];
+
+    for path in repo_tree
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+    {
+        let Some(index) = packages.iter().position(|package| package.contains(path)) else {
+            lines.insert(path.to_string(), None);
+            continue;
+        };
+
+        package_files[index] += 1;
+        let relative = &path[packages[index].path.len() + 1..];
+        if MANIFEST_FILES.contains(&relative) {
+            lines.insert(path.to_string(), None);
+        }
+    }
+
+    for (package, files) in packages.iter().zip(package_files) {
+        lines.insert(format!("{}/", package.path), Some(files));
+    }
+
+    lines
+        .into_iter()
+        .map(|(line, files)| match files {
+            Some(files) => format!("{line} (package, {files} files)"),
+            None => line,
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Used when a package could not be planned: a single overview page built
+/// from its top-level files.
+pub fn fallback_plan(package: &Package, repo_tree: &str) -> WikiStructure {
+    let relevant_files = package
+        .tree(repo_tree)
+        .lines()
+        .filter(|path| !path[package.path.len() + 1..].contains('/'))
+        .filter(|path| {
+            let name = path.rsplit('/').next().unwrap_or_default();
+            MANIFEST_FILES.contains(&name) || name.to_lowercase().starts_with("readme")
+        })
+        .map(str::to_string)
+        .collect();
+
+    WikiStructure {
+        title: package.path.clone(),
+        description: format!("Paquete {} del repositorio.", package.path),
+        sections: vec![WikiSection {
+            id: "overview".to_string(),
+            title: "Vision general".to_string(),
+            pages: vec!["overview".to_string()],
+            subsections: Vec::new(),
+        }],
+        pages: vec![WikiPage {
+            id: "overview".to_string(),
+            title: format!("Vision general de {}", package.path),
+            description: format!(
+                "Proposito, estructura y puntos de entrada del paquete {}.",
+                package.path
+            ),
+            importance: Importance::Medium,
+            relevant_files,
+            related_pages: Vec::new(),
+            parent_section: Some("overview".to_string()),
+        }],
+    }
+}
+
+/// Appends one section per package to the top-level plan. The package's own
+/// sections become its subsections and all of its ids are prefixed so they
+/// cannot collide with the rest of the wiki. References to ids the package
+/// plan does not define are dropped, unless they name a top-level page.
+pub fn merge_plans(root: WikiStructure, plans: Vec<(Package, WikiStructure)>) -> WikiStructure {
+    let mut merged = root;
+    let root_pages = merged
+        .pages
+        .iter()
+        .map(|page| page.id.clone())
+        .collect::<HashSet<_>>();
+
+    for (package, plan) in plans {
+        let prefix = package.id_prefix();
+        let rename = |id: &str| format!("{prefix}--{id}");
+        let package_section = prefix.clone();
+
+        let page_ids = plan
+            .pages
+            .iter()
+            .map(|page| page.id.as_str())
+            .collect::<HashSet<_>>();
+        let section_ids = plan
+            .sections
+            .iter()
+            .map(|section| section.id.as_str())
+            .collect::<HashSet<_>>();
+        let nested = plan
+            .sections
+            .iter()
+            .flat_map(|section| {
+                section
+                    .subsections
+                    .iter()
+                    .filter(move |id| **id != section.id)
+            })
+            .map(String::as_str)
+            .collect::<HashSet<_>>();
+        let grouped = plan
+            .sections
+            .iter()
+            .flat_map(|section| section.pages.iter().map(String::as_str))
+            .collect::<HashSet<_>>();
+
+        let title = if plan.title.trim().is_empty() {
+            package.path.clone()
+        } else {
+            format!("{} ({})", plan.title.trim(), package.path)
+        };
+
+        merged.sections.push(WikiSection {
+            id: package_section.clone(),
+            title,
+            pages: plan
+                .pages
+                .iter()
+                .filter(|page| !grouped.contains(page.id.as_str()))
+                .map(|page| rename(&page.id))
+                .collect(),
+            subsections: plan
+                .sections
+                .iter()
+                .filter(|section| !nested.contains(section.id.as_str()))
+                .map(|section| rename(&section.id))
+                .collect(),
+        });
+
+        for section in &plan.sections {
+            merged.sections.push(WikiSection {
+                id: rename(&section.id),
+                title: section.title.clone(),
+                pages: section
+                    .pages
+                    .iter()
+                    .filter(|id| page_ids.contains(id.as_str()))
+                    .map(|id| rename(id))
+                    .collect(),
+                subsections: section
+                    .subsections
+                    .iter()
+                    .filter(|id| **id != section.id && section_ids.contains(id.as_str()))
+                    .map(|id| rename(id))
+                    .collect(),
+            });
+        }
+
+        for page in &plan.pages {
+            let parent_section = match page.parent_section.as_deref() {
+                Some(id) if section_ids.contains(id) => rename(id),
+                _ => package_section.clone(),
+            };
+
+            merged.pages.push(WikiPage {
+                id: rename(&page.id),
+                title: page.title.clone(),
+                description: page.description.clone(),
+                importance: page.importance.clone(),
+                relevant_files: page.relevant_files.clone(),
+                related_pages: page
+                    .related_pages
+                    .iter()
+                    .filter_map(|id| {
+                        if page_ids.contains(id.as_str()) {
+                            Some(rename(id))
+                        } else {
+                            root_pages.contains(id).then(|| id.clone())
+                        }
+                    })
+                    .collect(),
+                parent_section: Some(parent_section),
+            });
+        }
+    }
+
+    merged
+}
@@ -1,11 +1,17 @@
+use crate::budget::PromptBudget;
+use crate::config::GenerationParams;
 use crate::llm::LlmClient;
 use crate::prompts::PromptStore;
 use anyhow::Result;
 
+const PROMPT_KEY: &str = "writer/writer";
+
 #[derive(Clone)]
 pub struct WriterAgent {
     llm: LlmClient,
     prompts: PromptStore,
+    params: GenerationParams,
+    budget: PromptBudget,
 }
 
 #[derive(Clone)]
@@ -20,13 +26,55 @@ pub struct WriterInput {
 }
 
 impl WriterAgent {
-    pub fn new(llm: LlmClient, prompts: PromptStore) -> Self {
-        Self { llm, prompts }
+    pub fn new(
+        llm: LlmClient,
+        prompts: PromptStore,
+        params: GenerationParams,
+        budget: PromptBudget,
+    ) -> Self {
+        Self {
+            llm,
+            prompts,
+            params,
+            budget,
+        }
+    }
+
+    /// Tokens the prompt leaves for `relevant_sources` and `files_content`.
+    pub fn sources_budget(&self, input: &WriterInput) -> Result<usize> {
+        let fixed = self.render(WriterInput {
+            relevant_sources: String::new(),
+            files_content: String::new(),
+            ..input.clone()
+        })?;
+
+        Ok(self.budget.available(&fixed, self.params.max_tokens))
+    }
+
+    /// The chat model and prompt template pages are written with; a change
+    /// to either means earlier pages can no longer be reused.
+    pub fn fingerprint(&self) -> Result<String> {
+        let template = self.prompts.template(PROMPT_KEY)?;
+        Ok(format!("{}\n{template}", self.llm.chat_model()))
     }
 
     pub async fn run(&self, input: WriterInput) -> Result<String> {
-        let prompt = self.prompts.render(
-            "writer/writer",
+        let prompt = self.render(input)?;
+
+        let markdown = self
+            .llm
+            .chat(
+                &prompt,
+                self.params.temperature,
+                Some(self.params.max_tokens),
+            )
+            .await?;
+        Ok(markdown.trim().to_string())
+    }
+
+    fn render(&self, input: WriterInput) -> Result<String> {
+        self.prompts.render(
+            PROMPT_KEY,
             &[
                 ("project_overview", input.project_overview),
                 ("project_features", input.project_features),
@@ -36,9 +84,6 @@ impl WriterAgent {
                 ("relevant_sources", input.relevant_sources),
                 ("files_content", input.files_content),
             ],
-        )?;
-
-        let markdown = self.llm.chat(&prompt, 0.1, Some(5000)).await?;
-        Ok(markdown.trim().to_string())
+        )
     }
 }
@@ -1,50 +1,190 @@
+use crate::agents::answer::AnswerAgent;
+use crate::agents::chat::ChatAgent;
 use crate::agents::planner::PlannerAgent;
 use crate::agents::writer::WriterAgent;
-use crate::config::AppConfig;
-use crate::docs::DocumentationGenerator;
+use crate::budget::PromptBudget;
+use crate::config::{AppConfig, RerankMode};
+use crate::docs::{DocumentationGenerator, RunInfo, SiteExporter, WikiVersions, tree_fingerprint};
 use crate::error::ApiError;
-use crate::llm::LlmClient;
-use crate::models::{DocGenerationInput, DocGenerationResponse};
+use crate::jobs::{JobHandle, JobRegistry};
+use crate::llm::{ChatStreamEvent, LlmClient, LlmOptions};
+use crate::models::{
+    AskInput, AskResponse, AskStreamDone, ChatRole, ChatSession, Citation, CreateSessionInput,
+    DocGenerationAccepted, DocGenerationInput, DocGenerationResponse, IssueResolution, JobPhase,
+    JobStatus, ProjectIndex, RetrievedChunk, SessionMessage, SessionMessageInput,
+    SiteExportResponse, WikiModels, WikiRollbackInput, WikiVersion,
+};
 use crate::prompts::PromptStore;
-use crate::rag::RagEngine;
-use crate::rag::qdrant::QdrantClient;
-use axum::extract::State;
-use axum::http::HeaderMap;
+use crate::rag::qdrant::{QdrantClient, collection_name_for};
+use crate::rag::{
+    ChunkSizes, IndexRegistry, IndexingOptions, LexicalStore, RagEngine, Reranker, RetrievalOptions,
+};
+use crate::resilience::{BreakerOptions, Upstream, UpstreamMetricsSnapshot};
+use crate::sessions::SessionStore;
+use anyhow::Context;
+use axum::extract::{Path, State};
 use axum::http::header::AUTHORIZATION;
+use axum::http::{HeaderMap, StatusCode};
 use axum::response::IntoResponse;
+use axum::response::sse::{Event, KeepAlive, Sse};
 use axum::{
     Json, Router,
     routing::{get, post},
 };
+use chrono::Utc;
+use futures::stream::{self, BoxStream, Stream, StreamExt};
+use serde::Serialize;
+use std::convert::Infallible;
 use std::sync::Arc;
-use tracing::{error, info, warn};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+use uuid::Uuid;
 
 #[derive(Clone)]
 pub struct AppState {
     config: Arc<AppConfig>,
+    upstreams: Vec<Upstream>,
     planner: PlannerAgent,
+    answer: AnswerAgent,
+    chat: ChatAgent,
     rag: RagEngine,
     docs_generator: DocumentationGenerator,
+    wiki_versions: WikiVersions,
+    site_exporter: SiteExporter,
+    jobs: JobRegistry,
+    sessions: SessionStore,
 }
 
 impl AppState {
     pub async fn new(config: Arc<AppConfig>) -> anyhow::Result<Self> {
-        let prompts = PromptStore::load(&config.prompts_dir).await?;
-        let llm = LlmClient::new(config.ollama_url.clone());
+        let prompts = PromptStore::load(&config.storage.prompts_dir).await?;
+        let breaker = BreakerOptions {
+            failure_threshold: config.resilience.failure_threshold,
+            open_for: Duration::from_secs(config.resilience.open_secs),
+        };
+        let llm_upstream = Upstream::new("llm", breaker);
+        let qdrant_upstream = Upstream::new("qdrant", breaker);
+        let rerank_upstream = Upstream::new("rerank", breaker);
+
+        let llm = LlmClient::new(
+            config.llm.url.clone(),
+            LlmOptions {
+                chat_model: config.llm.chat_model.clone(),
+                embed_model: config.llm.embed_model.clone(),
+                api_key: config.llm.api_key.clone(),
+                chat_retry: config.resilience.chat.into(),
+                embed_retry: config.resilience.embedding.into(),
+            },
+            llm_upstream.clone(),
+        );
         llm.check_connection().await?;
 
-        let planner = PlannerAgent::new(llm.clone(), prompts.clone());
-        let writer = WriterAgent::new(llm.clone(), prompts);
+        let embed_dimension = llm.probe_embedding_dimension().await?;
+        if let Some(expected) = config.llm.embed_dimension
+            && expected != embed_dimension
+        {
+            anyhow::bail!(
+                "llm.embed_dimension is {expected} but embedding model {} produces {embed_dimension}-dimension vectors",
+                config.llm.embed_model
+            );
+        }
+        info!(
+            model = %config.llm.embed_model,
+            dimension = embed_dimension,
+            "embedding model probed"
+        );
 
-        let qdrant = QdrantClient::new(config.qdrant_url.clone());
-        let rag = RagEngine::new(qdrant, llm);
-        let docs_generator = DocumentationGenerator::new(writer, rag.clone());
+        let generation = &config.generation;
+        let budget = PromptBudget::new(config.llm.context_tokens)?;
+        let planner = PlannerAgent::new(
+            llm.clone(),
+            prompts.clone(),
+            generation.planner,
+            budget.clone(),
+        );
+        let writer = WriterAgent::new(
+            llm.clone(),
+            prompts.clone(),
+            generation.writer,
+            budget.clone(),
+        );
+        let answer = AnswerAgent::new(llm.clone(), prompts.clone(), generation.answer);
+        let chat = ChatAgent::new(
+            llm.clone(),
+            prompts.clone(),
+            generation.chat,
+            budget.clone(),
+            config.sessions.history_tokens,
+        );
+        let reranker = match config.rerank.mode {
+            RerankMode::Off => Reranker::Disabled,
+            RerankMode::Llm => Reranker::Llm {
+                llm: Box::new(llm.clone()),
+                prompts,
+                params: generation.rerank,
+            },
+            RerankMode::Endpoint => Reranker::endpoint(
+                &config.llm.url,
+                config.rerank.model.clone(),
+                config.llm.api_key.clone(),
+                rerank_upstream.clone(),
+                config.resilience.rerank.into(),
+            )?,
+        };
+
+        let collection = collection_name_for(&config.llm.embed_model, embed_dimension);
+        let qdrant = QdrantClient::new(
+            config.qdrant.url.clone(),
+            collection,
+            embed_dimension,
+            qdrant_upstream.clone(),
+            config.resilience.qdrant.into(),
+        );
+        qdrant.ensure_collection().await?;
+        let indexing = IndexingOptions {
+            batch_size: config.indexing.batch_size,
+            concurrency: config.indexing.concurrency,
+            chunk_sizes: ChunkSizes {
+                markdown: config.indexing.markdown_chunk_size,
+                code: config.indexing.code_chunk_size,
+                text: config.indexing.text_chunk_size,
+            },
+        };
+        let retrieval = RetrievalOptions {
+            vector_weight: config.retrieval.vector_weight,
+            lexical_weight: config.retrieval.lexical_weight,
+            max_chunks_per_file: config.retrieval.max_chunks_per_file,
+            max_chunks: config.retrieval.max_chunks,
+            rerank_candidates: config.rerank.candidates,
+        };
+        let lexical = LexicalStore::new(config.storage.lexical_index_dir.clone());
+        let registry = IndexRegistry::load(config.storage.index_registry_path.clone()).await?;
+        let rag = RagEngine::new(
+            qdrant, llm, lexical, registry, reranker, indexing, retrieval,
+        );
+        let docs_generator =
+            DocumentationGenerator::new(writer, rag.clone(), budget, config.jobs.page_concurrency);
+        let wiki_versions = WikiVersions::new(
+            config.storage.output_dir.clone(),
+            config.storage.retained_versions,
+        );
+        let site_exporter = SiteExporter::new(config.storage.export_assets_dir.clone());
+        let jobs = JobRegistry::new(config.jobs.workers);
+        let sessions = SessionStore::new(config.storage.sessions_dir.clone());
 
         Ok(Self {
             config,
+            upstreams: vec![llm_upstream, qdrant_upstream, rerank_upstream],
             planner,
+            answer,
+            chat,
             rag,
             docs_generator,
+            wiki_versions,
+            site_exporter,
+            jobs,
+            sessions,
         })
     }
 }
@@ -52,6 +192,21 @@ impl AppState {
 pub fn build_router(state: AppState) -> Router {
     Router::new()
         .route("/docs-gen", post(docs_gen))
+        .route("/jobs/{id}", get(job_status))
+        .route("/ask", post(ask))
+        .route("/ask/stream", post(ask_stream))
+        .route("/sessions", post(create_session))
+        .route("/sessions/{id}", get(get_session))
+        .route(
+            "/sessions/{id}/messages",
+            get(list_session_messages).post(post_session_message),
+        )
+        .route("/admin/projects/{id}/index", get(project_index))
+        .route("/admin/projects/{id}/reembed", post(reembed_project))
+        .route("/admin/projects/{id}/wiki/versions", get(wiki_versions))
+        .route("/admin/projects/{id}/wiki/rollback", post(rollback_wiki))
+        .route("/admin/projects/{id}/wiki/export", post(export_wiki))
+        .route("/metrics", get(metrics))
         .route("/health", get(health))
         .with_state(state)
 }
@@ -60,67 +215,632 @@ async fn health() -> impl IntoResponse {
     Json(serde_json::json!({ "status": "ok" }))
 }
 
+async fn metrics(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<UpstreamMetricsSnapshot>>, ApiError> {
+    validate_api_key(&state.config, &headers)?;
+
+    Ok(Json(
+        state.upstreams.iter().map(Upstream::snapshot).collect(),
+    ))
+}
+
 async fn docs_gen(
     State(state): State<AppState>,
     headers: HeaderMap,
     Json(payload): Json<DocGenerationInput>,
-) -> Result<Json<DocGenerationResponse>, ApiError> {
+) -> Result<(StatusCode, Json<DocGenerationAccepted>), ApiError> {
     validate_api_key(&state.config, &headers)?;
 
     validate_payload(&payload)?;
 
-    info!(repo_id = %payload.repo_id, "planner started");
-    let planner_output = state
-        .planner
-        .run(&payload.repo_path, &payload.repo_tree)
+    let repo_id = payload.repo_id;
+    let job_state = state.clone();
+    let job_id = state
+        .jobs
+        .enqueue(repo_id, move |job| run_docs_job(job_state, job, payload))
+        .await;
+
+    info!(repo_id = %repo_id, job_id = %job_id, "documentation job queued");
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(DocGenerationAccepted {
+            job_id,
+            repo_id,
+            status_url: format!("/jobs/{job_id}"),
+        }),
+    ))
+}
+
+async fn job_status(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(job_id): Path<Uuid>,
+) -> Result<Json<JobStatus>, ApiError> {
+    validate_api_key(&state.config, &headers)?;
+
+    state
+        .jobs
+        .get(job_id)
+        .await
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found(format!("Job {job_id} not found")))
+}
+
+async fn ask(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<AskInput>,
+) -> Result<Json<AskResponse>, ApiError> {
+    validate_api_key(&state.config, &headers)?;
+
+    let question = payload.question.trim();
+    if question.is_empty() {
+        return Err(ApiError::bad_request("question cannot be empty"));
+    }
+
+    info!(repo_id = %payload.repo_id, "question received");
+    let chunks = state
+        .rag
+        .retrieve_question_context(payload.repo_id, question)
+        .await
+        .map_err(|error| ApiError::internal(format!("Retrieval failed: {error}")))?;
+
+    let answer = state
+        .answer
+        .run(question, &chunks)
+        .await
+        .map_err(|error| ApiError::internal(format!("Answer generation failed: {error}")))?;
+
+    let citations = build_citations(&answer, &chunks);
+
+    Ok(Json(AskResponse {
+        repo_id: payload.repo_id,
+        question: question.to_string(),
+        answer,
+        citations,
+    }))
+}
+
+async fn ask_stream(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<AskInput>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    validate_api_key(&state.config, &headers)?;
+
+    let question = payload.question.trim();
+    if question.is_empty() {
+        return Err(ApiError::bad_request("question cannot be empty"));
+    }
+
+    info!(repo_id = %payload.repo_id, "streamed question received");
+    let chunks = state
+        .rag
+        .retrieve_question_context(payload.repo_id, question)
+        .await
+        .map_err(|error| ApiError::internal(format!("Retrieval failed: {error}")))?;
+
+    let tokens = state
+        .answer
+        .run_stream(question, &chunks)
+        .await
+        .map_err(|error| ApiError::internal(format!("Answer generation failed: {error}")))?;
+
+    let (sender, receiver) = mpsc::channel(64);
+    tokio::spawn(relay_answer_stream(tokens, chunks, sender));
+
+    let events = stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|event| (Ok(event), receiver))
+    });
+
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+/// Forwards tokens as `token` events and finishes with a `done` event carrying
+/// citations and usage. Stops, cancelling the upstream request, as soon as the
+/// client disconnects.
+async fn relay_answer_stream(
+    mut tokens: BoxStream<'static, anyhow::Result<ChatStreamEvent>>,
+    chunks: Vec<RetrievedChunk>,
+    sender: mpsc::Sender<Event>,
+) {
+    let mut answer = String::new();
+    let mut usage = None;
+
+    loop {
+        let item = tokio::select! {
+            item = tokens.next() => item,
+            _ = sender.closed() => {
+                info!("client disconnected, cancelling answer stream");
+                return;
+            }
+        };
+
+        let event = match item {
+            Some(Ok(ChatStreamEvent::Token(token))) => {
+                let event = sse_event("token", &serde_json::json!({ "token": token }));
+                answer.push_str(&token);
+                event
+            }
+            Some(Ok(ChatStreamEvent::Usage(value))) => {
+                usage = Some(value);
+                continue;
+            }
+            Some(Err(error)) => {
+                let event = sse_event("error", &serde_json::json!({ "error": error.to_string() }));
+                let _ = sender.send(event).await;
+                return;
+            }
+            None => break,
+        };
+
+        if sender.send(event).await.is_err() {
+            return;
+        }
+    }
+
+    let done = AskStreamDone {
+        citations: build_citations(&answer, &chunks),
+        usage,
+    };
+
+    let _ = sender.send(sse_event("done", &done)).await;
+}
+
+fn sse_event(name: &str, data: &impl Serialize) -> Event {
+    Event::default()
+        .event(name)
+        .json_data(data)
+        .unwrap_or_else(|error| Event::default().event("error").data(error.to_string()))
+}
+
+async fn create_session(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<CreateSessionInput>,
+) -> Result<(StatusCode, Json<ChatSession>), ApiError> {
+    validate_api_key(&state.config, &headers)?;
+
+    let session = state
+        .sessions
+        .create(payload.repo_id)
+        .await
+        .map_err(|error| ApiError::internal(format!("Failed to create session: {error}")))?;
+
+    info!(repo_id = %session.repo_id, session_id = %session.id, "chat session created");
+    Ok((StatusCode::CREATED, Json(session)))
+}
+
+async fn get_session(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(session_id): Path<Uuid>,
+) -> Result<Json<ChatSession>, ApiError> {
+    validate_api_key(&state.config, &headers)?;
+
+    load_session(&state, session_id).await.map(Json)
+}
+
+async fn list_session_messages(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(session_id): Path<Uuid>,
+) -> Result<Json<Vec<SessionMessage>>, ApiError> {
+    validate_api_key(&state.config, &headers)?;
+
+    let session = load_session(&state, session_id).await?;
+    Ok(Json(session.messages))
+}
+
+/// Answers one turn: retrieval runs again for every message, using the previous
+/// user turn as extra context so short follow-ups still find the right code.
+async fn post_session_message(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(session_id): Path<Uuid>,
+    Json(payload): Json<SessionMessageInput>,
+) -> Result<Json<SessionMessage>, ApiError> {
+    validate_api_key(&state.config, &headers)?;
+
+    let content = payload.content.trim();
+    if content.is_empty() {
+        return Err(ApiError::bad_request("content cannot be empty"));
+    }
+
+    let session = load_session(&state, session_id).await?;
+
+    let query = match session
+        .messages
+        .iter()
+        .rev()
+        .find(|message| message.role == ChatRole::User)
+    {
+        Some(previous) => format!("{}\n{content}", previous.content),
+        None => content.to_string(),
+    };
+
+    info!(repo_id = %session.repo_id, session_id = %session_id, "chat message received");
+    let chunks = state
+        .rag
+        .retrieve_question_context(session.repo_id, &query)
+        .await
+        .map_err(|error| ApiError::internal(format!("Retrieval failed: {error}")))?;
+
+    let user_message = SessionMessage {
+        role: ChatRole::User,
+        content: content.to_string(),
+        created_at: Utc::now(),
+        citations: Vec::new(),
+    };
+
+    let answer = state
+        .chat
+        .run(&session.messages, content, &chunks)
+        .await
+        .map_err(|error| ApiError::internal(format!("Answer generation failed: {error}")))?;
+
+    let assistant_message = SessionMessage {
+        role: ChatRole::Assistant,
+        citations: build_citations(&answer, &chunks),
+        content: answer,
+        created_at: Utc::now(),
+    };
+
+    state
+        .sessions
+        .append(session_id, vec![user_message, assistant_message.clone()])
+        .await
+        .map_err(|error| ApiError::internal(format!("Failed to save session: {error}")))?
+        .ok_or_else(|| ApiError::not_found(format!("Session {session_id} not found")))?;
+
+    Ok(Json(assistant_message))
+}
+
+async fn load_session(state: &AppState, session_id: Uuid) -> Result<ChatSession, ApiError> {
+    state
+        .sessions
+        .get(session_id)
         .await
-        .map_err(|error| ApiError::internal(format!("Planner failed: {error}")))?;
+        .map_err(|error| ApiError::internal(format!("Failed to load session: {error}")))?
+        .ok_or_else(|| ApiError::not_found(format!("Session {session_id} not found")))
+}
+
+async fn project_index(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(repo_id): Path<Uuid>,
+) -> Result<Json<ProjectIndex>, ApiError> {
+    validate_api_key(&state.config, &headers)?;
 
-    info!(repo_id = %payload.repo_id, "indexation started");
     state
         .rag
-        .new_indexation(payload.repo_id, std::path::Path::new(&payload.repo_path))
+        .project_index(repo_id)
         .await
-        .map_err(|error| ApiError::internal(format!("Indexation failed: {error}")))?;
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found(format!("Repository {repo_id} has not been indexed")))
+}
+
+/// Moves a repository to the collection of the configured embedding model.
+async fn reembed_project(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(repo_id): Path<Uuid>,
+) -> Result<Json<ProjectIndex>, ApiError> {
+    validate_api_key(&state.config, &headers)?;
+
+    info!(repo_id = %repo_id, "re-embedding requested");
+    // Shares the lock documentation jobs hold, so an indexation of the same
+    // project cannot write to the collections being switched.
+    let _repo_guard = state.jobs.lock_repo(repo_id).await;
+    let entry = state
+        .rag
+        .reembed_project(repo_id)
+        .await
+        .map_err(|error| ApiError::internal(format!("Re-embedding failed: {error:#}")))?;
+
+    Ok(Json(entry))
+}
+
+async fn wiki_versions(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(repo_id): Path<Uuid>,
+) -> Result<Json<Vec<WikiVersion>>, ApiError> {
+    validate_api_key(&state.config, &headers)?;
 
-    info!(repo_id = %payload.repo_id, "markdown generation started");
     state
+        .wiki_versions
+        .list(repo_id)
+        .await
+        .map(Json)
+        .map_err(|error| ApiError::internal(format!("Failed to list wiki versions: {error:#}")))
+}
+
+/// Republishes an earlier wiki version, by default the one before the current.
+async fn rollback_wiki(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(repo_id): Path<Uuid>,
+    Json(payload): Json<WikiRollbackInput>,
+) -> Result<Json<Vec<WikiVersion>>, ApiError> {
+    validate_api_key(&state.config, &headers)?;
+
+    let version = state
+        .wiki_versions
+        .rollback(repo_id, payload.version.as_deref())
+        .await
+        .map_err(|error| ApiError::internal(format!("Rollback failed: {error:#}")))?
+        .ok_or_else(|| {
+            ApiError::not_found(format!("No wiki version to roll back to for {repo_id}"))
+        })?;
+
+    info!(repo_id = %repo_id, version, "wiki rolled back");
+    state
+        .wiki_versions
+        .list(repo_id)
+        .await
+        .map(Json)
+        .map_err(|error| ApiError::internal(format!("Failed to list wiki versions: {error:#}")))
+}
+
+/// Renders the published wiki as a static HTML site for offline reading.
+async fn export_wiki(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(repo_id): Path<Uuid>,
+) -> Result<Json<SiteExportResponse>, ApiError> {
+    validate_api_key(&state.config, &headers)?;
+
+    let version = state
+        .wiki_versions
+        .current_version(repo_id)
+        .await
+        .map_err(|error| ApiError::internal(format!("Failed to read wiki version: {error:#}")))?
+        .ok_or_else(|| {
+            ApiError::not_found(format!("Repository {repo_id} has no published wiki"))
+        })?;
+
+    let output_dir = state.wiki_versions.export_dir(repo_id, &version);
+    let export = state
+        .site_exporter
+        .export(
+            &state.wiki_versions.version_dir(repo_id, &version),
+            &output_dir,
+        )
+        .await
+        .map_err(|error| ApiError::internal(format!("Export failed: {error:#}")))?;
+
+    Ok(Json(SiteExportResponse {
+        repo_id,
+        version,
+        output_path: output_dir.to_string_lossy().to_string(),
+        pages: export.pages,
+    }))
+}
+
+async fn run_docs_job(
+    state: AppState,
+    job: JobHandle,
+    payload: DocGenerationInput,
+) -> anyhow::Result<DocGenerationResponse> {
+    let job_id = job.id();
+    let started_at = Utc::now();
+
+    let published_dir = state.wiki_versions.current_dir(payload.repo_id);
+    let previous = if payload.force {
+        None
+    } else {
+        state.docs_generator.load_previous(&published_dir).await
+    };
+    let tree_hash = tree_fingerprint(&payload.repo_tree);
+
+    job.set_phase(JobPhase::Planning).await;
+    let reusable_plan = previous
+        .as_ref()
+        .filter(|previous| previous.manifest.tree_hash == tree_hash)
+        .map(|previous| {
+            (
+                previous.manifest.structure.clone(),
+                previous.manifest.planner_inputs.clone(),
+            )
+        });
+    let planner_reused = reusable_plan.is_some();
+    let mut prompt_trims = Vec::new();
+    let mut structure_issues = Vec::new();
+    let (planner_output, planner_inputs) = match reusable_plan {
+        Some(plan) => {
+            info!(repo_id = %payload.repo_id, job_id = %job_id, "file tree unchanged, reusing planner output");
+            plan
+        }
+        None => {
+            info!(repo_id = %payload.repo_id, job_id = %job_id, "planner started");
+            let output = state
+                .planner
+                .run(&payload.repo_path, &payload.repo_tree)
+                .await
+                .context("Planner failed")?;
+            info!(repo_id = %payload.repo_id, job_id = %job_id, inputs = ?output.inputs, "planner finished");
+            prompt_trims.extend(output.trims);
+            structure_issues = output.issues;
+            (output.structure, output.inputs)
+        }
+    };
+
+    job.set_phase(JobPhase::Indexing).await;
+    info!(repo_id = %payload.repo_id, job_id = %job_id, "indexation started");
+    state
+        .rag
+        .new_indexation(payload.repo_id, std::path::Path::new(&payload.repo_path))
+        .await
+        .context("Indexation failed")?;
+
+    let (version, version_dir) = state
+        .wiki_versions
+        .create(payload.repo_id, payload.commit_sha.as_deref(), job_id)
+        .await?;
+
+    info!(repo_id = %payload.repo_id, job_id = %job_id, version, "markdown generation started");
+    let generated = state
         .docs_generator
-        .clone()
         .generate(
-            &state.config.wiki_output_dir,
-            payload.repo_id,
+            &version_dir,
+            RunInfo {
+                repo_id: payload.repo_id,
+                version: version.clone(),
+                commit_sha: payload.commit_sha.clone(),
+                tree_hash,
+                planner_inputs: planner_inputs.clone(),
+                models: wiki_models(&state, payload.repo_id).await,
+                started_at,
+            },
             planner_output,
+            previous,
+            &job,
         )
         .await
-        .map(|result| {
-            let has_errors = !result.errors.is_empty();
-            if has_errors {
-                warn!(
-                    repo_id = %payload.repo_id,
-                    count = result.errors.len(),
-                    "documentation generated with partial errors"
+        .context("Documentation generation failed")
+        .and_then(|result| {
+            // Failed pages keep their previous version where there is one,
+            // so a few errors still publish; past the configured ratio, or
+            // with nothing newly written, the current version stays.
+            let total = result.generated_pages + result.errors.len();
+            let failed_ratio = result.errors.len() as f64 / total.max(1) as f64;
+            if result.generated_pages == 0 || failed_ratio > state.config.jobs.max_failed_page_ratio
+            {
+                anyhow::bail!(
+                    "Documentation generation failed: {} of {total} pages errored",
+                    result.errors.len()
                 );
-            } else {
-                info!(repo_id = %payload.repo_id, "documentation generated successfully");
             }
+            Ok(result)
+        });
 
-            Json(DocGenerationResponse {
-                repo_id: payload.repo_id,
-                message: if has_errors {
-                    "Documentation generated with partial failures".to_string()
-                } else {
-                    "Documentation generated successfully".to_string()
-                },
-                generated_pages: result.generated_pages,
-                output_path: result.output_dir.to_string_lossy().to_string(),
-                errors: result.errors,
-            })
-        })
-        .map_err(|error| {
-            error!(repo_id = %payload.repo_id, "docs generation failed: {error}");
-            ApiError::internal(format!("Documentation generation failed: {error}"))
+    // A failed run leaves the published version untouched.
+    let result = match generated {
+        Ok(result) => result,
+        Err(error) => {
+            if let Err(cleanup_error) = state.wiki_versions.discard(payload.repo_id, &version).await
+            {
+                warn!(repo_id = %payload.repo_id, version, "failed to remove unpublished wiki version: {cleanup_error:#}");
+            }
+            return Err(error);
+        }
+    };
+
+    state
+        .wiki_versions
+        .activate(payload.repo_id, &version)
+        .await
+        .context("Failed to publish wiki version")?;
+
+    prompt_trims.extend(result.prompt_trims);
+    if !prompt_trims.is_empty() {
+        warn!(
+            repo_id = %payload.repo_id,
+            job_id = %job_id,
+            count = prompt_trims.len(),
+            "prompt sections trimmed to fit the context window"
+        );
+    }
+
+    let unresolved = structure_issues
+        .iter()
+        .filter(|issue| issue.resolution == IssueResolution::Unresolved)
+        .count();
+    if !structure_issues.is_empty() {
+        warn!(
+            repo_id = %payload.repo_id,
+            job_id = %job_id,
+            count = structure_issues.len(),
+            unresolved,
+            "planner structure had issues"
+        );
+    }
+
+    let has_errors = !result.errors.is_empty();
+    if has_errors {
+        warn!(
+            repo_id = %payload.repo_id,
+            job_id = %job_id,
+            count = result.errors.len(),
+            carried_over = result.carried_over_pages,
+            "documentation generated with partial errors"
+        );
+    } else {
+        info!(repo_id = %payload.repo_id, job_id = %job_id, "documentation generated successfully");
+    }
+
+    Ok(DocGenerationResponse {
+        repo_id: payload.repo_id,
+        message: if has_errors {
+            "Documentation generated with partial failures".to_string()
+        } else {
+            "Documentation generated successfully".to_string()
+        },
+        generated_pages: result.generated_pages,
+        reused_pages: result.reused_pages,
+        carried_over_pages: result.carried_over_pages,
+        planner_reused,
+        planner_inputs,
+        prompt_trims,
+        structure_issues,
+        version,
+        output_path: published_dir.to_string_lossy().to_string(),
+        errors: result.errors,
+    })
+}
+
+/// Models that produced a wiki. The embedding model is the one the
+/// repository is indexed with, which may lag behind the configured one.
+async fn wiki_models(state: &AppState, repo_id: Uuid) -> WikiModels {
+    let embedding = state
+        .rag
+        .project_index(repo_id)
+        .await
+        .map(|entry| entry.embed_model)
+        .unwrap_or_else(|| state.config.llm.embed_model.clone());
+
+    let rerank = match state.config.rerank.mode {
+        RerankMode::Off => None,
+        RerankMode::Llm => Some(state.config.llm.chat_model.clone()),
+        RerankMode::Endpoint => Some(state.config.rerank.model.clone()),
+    };
+
+    WikiModels {
+        chat: state.config.llm.chat_model.clone(),
+        embedding,
+        rerank,
+    }
+}
+
+/// Keeps only the sources the answer cites as `[n]`, or all of them when it cites none.
+fn build_citations(answer: &str, chunks: &[RetrievedChunk]) -> Vec<Citation> {
+    let cited = (1..=chunks.len())
+        .filter(|index| answer.contains(&format!("[{index}]")))
+        .collect::<Vec<_>>();
+
+    let indices = if cited.is_empty() {
+        (1..=chunks.len()).collect()
+    } else {
+        cited
+    };
+
+    indices
+        .into_iter()
+        .map(|index| {
+            let chunk = &chunks[index - 1];
+            Citation {
+                index,
+                source: chunk.source.clone(),
+                start_line: chunk.start_line,
+                end_line: chunk.end_line,
+                symbol: chunk.symbol.as_ref().map(|symbol| symbol.name.clone()),
+            }
         })
+        .collect()
 }
 
 fn validate_api_key(config: &AppConfig, headers: &HeaderMap) -> Result<(), ApiError> {
@@ -130,7 +850,7 @@ fn validate_api_key(config: &AppConfig, headers: &HeaderMap) -> Result<(), ApiEr
         .ok_or_else(|| ApiError::unauthorized("Missing Authorization header"))?;
 
     let token = header.strip_prefix("Bearer ").unwrap_or_default().trim();
-    if token.is_empty() || token != config.wiki_service_api_key {
+    if token.is_empty() || token != config.server.api_key {
         return Err(ApiError::unauthorized("Invalid API key"));
     }
 
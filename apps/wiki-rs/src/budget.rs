@@ -0,0 +1,141 @@
+use anyhow::{Result, anyhow};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use tiktoken_rs::CoreBPE;
+
+/// Share of the context window kept free: the tokenizer only approximates
+/// the one of the model actually serving the prompt.
+const SAFETY_MARGIN_PERCENT: usize = 10;
+
+/// Counts tokens and fits prompt sections into the model's context window.
+#[derive(Clone)]
+pub struct PromptBudget {
+    bpe: Arc<CoreBPE>,
+    context_tokens: usize,
+}
+
+impl PromptBudget {
+    pub fn new(context_tokens: usize) -> Result<Self> {
+        let bpe = tiktoken_rs::o200k_base()
+            .map_err(|error| anyhow!("failed to load tokenizer: {error}"))?;
+
+        Ok(Self {
+            bpe: Arc::new(bpe),
+            context_tokens,
+        })
+    }
+
+    pub fn count(&self, text: &str) -> usize {
+        self.bpe.encode_ordinary(text).len()
+    }
+
+    /// Tokens left for the variable sections of a prompt once its fixed part
+    /// and the reply are accounted for.
+    pub fn available(&self, fixed_prompt: &str, reply_tokens: u32) -> usize {
+        let usable = self.context_tokens * (100 - SAFETY_MARGIN_PERCENT) / 100;
+        usable.saturating_sub(self.count(fixed_prompt) + reply_tokens as usize)
+    }
+
+    /// Keeps whole lines from the start of `text` while they fit in `max_tokens`.
+    pub fn truncate_lines(&self, text: &str, max_tokens: usize) -> String {
+        let marker = "[... truncated]";
+        let limit = max_tokens.saturating_sub(self.count(marker) + 1);
+
+        let mut used = 0;
+        let mut kept = Vec::new();
+        for line in text.lines() {
+            let cost = self.count(line) + 1;
+            if used + cost > limit {
+                break;
+            }
+
+            used += cost;
+            kept.push(line);
+        }
+
+        if kept.len() == text.lines().count() {
+            return text.to_string();
+        }
+
+        kept.push(marker);
+        kept.join("\n")
+    }
+
+    /// Fits a file tree (one path per line) into `max_tokens` by folding the
+    /// deepest directories into one summary line each, as shallow as needed.
+    /// Returns the tree and the depth it was collapsed to, if any.
+    pub fn collapse_tree(&self, tree: &str, max_tokens: usize) -> (String, Option<usize>) {
+        if self.count(tree) <= max_tokens {
+            return (tree.to_string(), None);
+        }
+
+        let paths = tree
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>();
+        let max_depth = paths
+            .iter()
+            .map(|path| path.split('/').count())
+            .max()
+            .unwrap_or(1);
+
+        for depth in (1..max_depth).rev() {
+            let collapsed = collapse_to_depth(&paths, depth);
+            if self.count(&collapsed) <= max_tokens {
+                return (collapsed, Some(depth));
+            }
+        }
+
+        let collapsed = collapse_to_depth(&paths, 1);
+        (self.truncate_lines(&collapsed, max_tokens), Some(1))
+    }
+}
+
+/// Lists paths up to `depth` components; deeper files are summarized per
+/// directory with their count and most common extensions.
+fn collapse_to_depth(paths: &[&str], depth: usize) -> String {
+    let mut lines = BTreeMap::new();
+    let mut folded: HashMap<String, (usize, HashMap<String, usize>)> = HashMap::new();
+
+    for path in paths {
+        let components = path.split('/').collect::<Vec<_>>();
+        if components.len() <= depth {
+            lines.insert(path.to_string(), None);
+            continue;
+        }
+
+        let prefix = components[..depth].join("/");
+        let extension = components
+            .last()
+            .and_then(|name| name.rsplit_once('.'))
+            .map(|(_, extension)| format!(".{extension}"))
+            .unwrap_or_else(|| "no extension".to_string());
+
+        let (count, extensions) = folded.entry(prefix.clone()).or_default();
+        *count += 1;
+        *extensions.entry(extension).or_default() += 1;
+        lines.insert(format!("{prefix}/"), Some(prefix));
+    }
+
+    lines
+        .into_iter()
+        .map(|(line, prefix)| {
+            let Some((count, extensions)) = prefix.and_then(|prefix| folded.get(&prefix)) else {
+                return line;
+            };
+
+            let mut extensions = extensions.iter().collect::<Vec<_>>();
+            extensions.sort_by(|left, right| right.1.cmp(left.1).then(left.0.cmp(right.0)));
+            let summary = extensions
+                .into_iter()
+                .take(3)
+                .map(|(extension, count)| format!("{count} {extension}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            format!("{line} ({count} files: {summary})")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -1,40 +1,772 @@
-use anyhow::{Context, Result};
+use crate::resilience::RetryPolicy;
+use anyhow::{Context, Result, anyhow};
+use regex::{Captures, Regex};
+use serde::Deserialize;
 use std::env;
 use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
 
-#[derive(Clone, Debug)]
+const DEFAULT_CONFIG_PATH: &str = "/app/apps/wiki-rs/config/config.toml";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RerankMode {
+    Off,
+    Llm,
+    Endpoint,
+}
+
+impl FromStr for RerankMode {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "off" | "" => Ok(Self::Off),
+            "llm" => Ok(Self::Llm),
+            "endpoint" => Ok(Self::Endpoint),
+            other => Err(anyhow!("expected off, llm or endpoint, got {other}")),
+        }
+    }
+}
+
+/// Layered configuration: built-in defaults, then `config.toml` (with
+/// `${VAR:default}` interpolation), then the environment overrides.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct AppConfig {
-    pub wiki_service_api_key: String,
-    pub ollama_url: String,
-    pub qdrant_url: String,
-    pub wiki_output_dir: PathBuf,
+    pub server: ServerConfig,
+    pub llm: LlmConfig,
+    pub qdrant: QdrantConfig,
+    pub storage: StorageConfig,
+    pub jobs: JobsConfig,
+    pub indexing: IndexingConfig,
+    pub retrieval: RetrievalConfig,
+    pub rerank: RerankConfig,
+    pub sessions: SessionsConfig,
+    pub generation: GenerationConfig,
+    pub resilience: ResilienceConfig,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LlmConfig {
+    pub url: String,
+    pub api_key: Option<String>,
+    pub chat_model: String,
+    pub embed_model: String,
+    pub embed_dimension: Option<usize>,
+    /// Context window of the chat model; prompts are trimmed to fit it.
+    pub context_tokens: usize,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QdrantConfig {
+    pub url: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    pub output_dir: PathBuf,
     pub prompts_dir: PathBuf,
+    pub lexical_index_dir: PathBuf,
+    pub index_registry_path: PathBuf,
+    pub sessions_dir: PathBuf,
+    /// Stylesheet and scripts of the static HTML export, with optional `vendor/` libraries.
+    pub export_assets_dir: PathBuf,
+    /// Generated wiki versions kept per repository, besides the published one.
+    pub retained_versions: usize,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JobsConfig {
+    pub workers: usize,
+    /// Wiki pages written concurrently within a single job.
+    pub page_concurrency: usize,
+    /// Fraction of pages that may fail before a run is discarded instead of
+    /// published.
+    pub max_failed_page_ratio: f64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IndexingConfig {
+    pub batch_size: usize,
+    pub concurrency: usize,
+    pub markdown_chunk_size: usize,
+    pub code_chunk_size: usize,
+    pub text_chunk_size: usize,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetrievalConfig {
+    pub vector_weight: f32,
+    pub lexical_weight: f32,
+    pub max_chunks_per_file: usize,
+    pub max_chunks: usize,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RerankConfig {
+    pub mode: RerankMode,
+    pub model: String,
+    pub candidates: usize,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SessionsConfig {
+    pub history_tokens: usize,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GenerationConfig {
+    pub planner: GenerationParams,
+    pub writer: GenerationParams,
+    pub answer: GenerationParams,
+    pub chat: GenerationParams,
+    pub rerank: GenerationParams,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GenerationParams {
+    pub temperature: f32,
+    pub max_tokens: u32,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ResilienceConfig {
+    pub failure_threshold: u32,
+    pub open_secs: u64,
+    pub chat: RetryConfig,
+    pub embedding: RetryConfig,
+    pub qdrant: RetryConfig,
+    pub rerank: RetryConfig,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryConfig {
+    pub timeout_secs: u64,
+    pub max_attempts: u32,
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            host: "0.0.0.0".to_string(),
+            port: 3000,
+            api_key: String::new(),
+        }
+    }
+}
+
+impl Default for LlmConfig {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            api_key: None,
+            chat_model: "qwen3.5:9b".to_string(),
+            embed_model: "nomic-embed-text-v2-moe:latest".to_string(),
+            embed_dimension: None,
+            context_tokens: 32_768,
+        }
+    }
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            output_dir: PathBuf::from("/app/repos/wiki_output"),
+            prompts_dir: PathBuf::from("/app/apps/wiki-rs/config"),
+            lexical_index_dir: PathBuf::from("/app/repos/wiki_index"),
+            index_registry_path: PathBuf::from("/app/repos/wiki_index_registry.json"),
+            sessions_dir: PathBuf::from("/app/repos/wiki_sessions"),
+            export_assets_dir: PathBuf::from("/app/apps/wiki-rs/config/export"),
+            retained_versions: 5,
+        }
+    }
+}
+
+impl Default for JobsConfig {
+    fn default() -> Self {
+        Self {
+            workers: 2,
+            page_concurrency: 3,
+            max_failed_page_ratio: 0.5,
+        }
+    }
+}
+
+impl Default for IndexingConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 64,
+            concurrency: 4,
+            markdown_chunk_size: 1200,
+            code_chunk_size: 800,
+            text_chunk_size: 1000,
+        }
+    }
+}
+
+impl Default for RetrievalConfig {
+    fn default() -> Self {
+        Self {
+            vector_weight: 1.0,
+            lexical_weight: 1.0,
+            max_chunks_per_file: 3,
+            max_chunks: 14,
+        }
+    }
+}
+
+impl Default for RerankConfig {
+    fn default() -> Self {
+        Self {
+            mode: RerankMode::Off,
+            model: "bge-reranker-v2-m3".to_string(),
+            candidates: 30,
+        }
+    }
+}
+
+impl Default for SessionsConfig {
+    fn default() -> Self {
+        Self {
+            history_tokens: 4000,
+        }
+    }
+}
+
+impl Default for GenerationConfig {
+    fn default() -> Self {
+        Self {
+            planner: GenerationParams::new(0.0, 3000),
+            writer: GenerationParams::new(0.1, 5000),
+            answer: GenerationParams::new(0.1, 2000),
+            chat: GenerationParams::new(0.1, 2000),
+            rerank: GenerationParams::new(0.0, 1500),
+        }
+    }
+}
+
+impl Default for GenerationParams {
+    fn default() -> Self {
+        Self::new(0.1, 2000)
+    }
+}
+
+impl Default for ResilienceConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            open_secs: 30,
+            chat: RetryConfig::new(300, 3),
+            embedding: RetryConfig::new(60, 3),
+            qdrant: RetryConfig::new(30, 3),
+            rerank: RetryConfig::new(60, 3),
+        }
+    }
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self::new(30, 3)
+    }
+}
+
+impl RetryConfig {
+    fn new(timeout_secs: u64, max_attempts: u32) -> Self {
+        Self {
+            timeout_secs,
+            max_attempts,
+            base_delay_ms: 500,
+            max_delay_ms: 10_000,
+        }
+    }
+}
+
+impl From<RetryConfig> for RetryPolicy {
+    fn from(value: RetryConfig) -> Self {
+        Self {
+            timeout: Duration::from_secs(value.timeout_secs),
+            max_attempts: value.max_attempts,
+            base_delay: Duration::from_millis(value.base_delay_ms),
+            max_delay: Duration::from_millis(value.max_delay_ms),
+        }
+    }
+}
+
+impl GenerationParams {
+    fn new(temperature: f32, max_tokens: u32) -> Self {
+        Self {
+            temperature,
+            max_tokens,
+        }
+    }
 }
 
 impl AppConfig {
-    pub fn from_env() -> Result<Self> {
-        let wiki_service_api_key = get_required("WIKI_SERVICE_API_KEY")?;
-        let ollama_url = get_required("OLLAMA_URL")?;
-        let qdrant_url = get_required("QDRANT_URL")?;
-
-        let wiki_output_dir = env::var("WIKI_OUTPUT_DIR")
-            .map(PathBuf::from)
-            .unwrap_or_else(|_| PathBuf::from("/app/repos/wiki_output"));
-
-        let prompts_dir = env::var("WIKI_PROMPTS_DIR")
-            .map(PathBuf::from)
-            .unwrap_or_else(|_| PathBuf::from("/app/apps/wiki-rs/config"));
-
-        Ok(Self {
-            wiki_service_api_key,
-            ollama_url,
-            qdrant_url,
-            wiki_output_dir,
-            prompts_dir,
+    /// Reads `WIKI_CONFIG_PATH` (or the bundled `config.toml`), applies the
+    /// environment overrides and validates the result.
+    pub fn load() -> Result<Self> {
+        let explicit_path = env::var("WIKI_CONFIG_PATH")
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+            .map(PathBuf::from);
+        let path = explicit_path
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_PATH));
+
+        let mut config = match std::fs::read_to_string(&path) {
+            Ok(raw) => Self::from_toml(&raw)
+                .with_context(|| format!("invalid configuration file {}", path.display()))?,
+            Err(error)
+                if error.kind() == std::io::ErrorKind::NotFound && explicit_path.is_none() =>
+            {
+                Self::default()
+            }
+            Err(error) => {
+                return Err(error)
+                    .with_context(|| format!("failed to read configuration {}", path.display()));
+            }
+        };
+
+        config.apply_env()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn from_toml(raw: &str) -> Result<Self> {
+        let interpolated = interpolate(raw)?;
+        let mut config: Self = toml::from_str(&interpolated).map_err(|error| anyhow!("{error}"))?;
+
+        config.llm.api_key = config.llm.api_key.filter(|value| !value.trim().is_empty());
+
+        Ok(config)
+    }
+
+    /// Environment variables win over the file, keeping the names earlier
+    /// deployments already set.
+    fn apply_env(&mut self) -> Result<()> {
+        override_from_env(&mut self.server.host, "WIKI_HOST")?;
+        override_from_env(&mut self.server.port, "WIKI_PORT")?;
+        override_from_env(&mut self.server.api_key, "WIKI_SERVICE_API_KEY")?;
+
+        override_from_env(&mut self.llm.url, "OLLAMA_URL")?;
+        override_optional_from_env(&mut self.llm.api_key, "WIKI_LLM_API_KEY")?;
+        override_from_env(&mut self.llm.chat_model, "WIKI_CHAT_MODEL")?;
+        override_from_env(&mut self.llm.embed_model, "WIKI_EMBED_MODEL")?;
+        override_optional_from_env(&mut self.llm.embed_dimension, "WIKI_EMBED_DIMENSION")?;
+        override_from_env(&mut self.llm.context_tokens, "WIKI_CONTEXT_TOKENS")?;
+
+        override_from_env(&mut self.qdrant.url, "QDRANT_URL")?;
+
+        override_from_env(&mut self.storage.output_dir, "WIKI_OUTPUT_DIR")?;
+        override_from_env(&mut self.storage.prompts_dir, "WIKI_PROMPTS_DIR")?;
+        override_from_env(
+            &mut self.storage.lexical_index_dir,
+            "WIKI_LEXICAL_INDEX_DIR",
+        )?;
+        override_from_env(
+            &mut self.storage.index_registry_path,
+            "WIKI_INDEX_REGISTRY_PATH",
+        )?;
+        override_from_env(&mut self.storage.sessions_dir, "WIKI_SESSIONS_DIR")?;
+        override_from_env(
+            &mut self.storage.export_assets_dir,
+            "WIKI_EXPORT_ASSETS_DIR",
+        )?;
+        override_from_env(
+            &mut self.storage.retained_versions,
+            "WIKI_RETAINED_VERSIONS",
+        )?;
+
+        override_from_env(&mut self.jobs.workers, "WIKI_JOB_WORKERS")?;
+        override_from_env(&mut self.jobs.page_concurrency, "WIKI_PAGE_CONCURRENCY")?;
+        override_from_env(
+            &mut self.jobs.max_failed_page_ratio,
+            "WIKI_MAX_FAILED_PAGE_RATIO",
+        )?;
+
+        override_from_env(&mut self.indexing.batch_size, "WIKI_EMBED_BATCH_SIZE")?;
+        override_from_env(&mut self.indexing.concurrency, "WIKI_EMBED_CONCURRENCY")?;
+        override_from_env(
+            &mut self.indexing.markdown_chunk_size,
+            "WIKI_MARKDOWN_CHUNK_SIZE",
+        )?;
+        override_from_env(&mut self.indexing.code_chunk_size, "WIKI_CODE_CHUNK_SIZE")?;
+        override_from_env(&mut self.indexing.text_chunk_size, "WIKI_TEXT_CHUNK_SIZE")?;
+
+        override_from_env(
+            &mut self.retrieval.vector_weight,
+            "WIKI_RETRIEVAL_VECTOR_WEIGHT",
+        )?;
+        override_from_env(
+            &mut self.retrieval.lexical_weight,
+            "WIKI_RETRIEVAL_LEXICAL_WEIGHT",
+        )?;
+        override_from_env(
+            &mut self.retrieval.max_chunks_per_file,
+            "WIKI_RETRIEVAL_MAX_CHUNKS_PER_FILE",
+        )?;
+        override_from_env(&mut self.retrieval.max_chunks, "WIKI_RETRIEVAL_MAX_CHUNKS")?;
+
+        override_from_env(&mut self.rerank.mode, "WIKI_RERANK_MODE")?;
+        override_from_env(&mut self.rerank.model, "WIKI_RERANK_MODEL")?;
+        override_from_env(&mut self.rerank.candidates, "WIKI_RERANK_CANDIDATES")?;
+
+        override_from_env(
+            &mut self.sessions.history_tokens,
+            "WIKI_SESSION_HISTORY_TOKENS",
+        )?;
+
+        for (name, params) in [
+            ("PLANNER", &mut self.generation.planner),
+            ("WRITER", &mut self.generation.writer),
+            ("ANSWER", &mut self.generation.answer),
+            ("CHAT", &mut self.generation.chat),
+            ("RERANK", &mut self.generation.rerank),
+        ] {
+            override_from_env(&mut params.temperature, &format!("WIKI_{name}_TEMPERATURE"))?;
+            override_from_env(&mut params.max_tokens, &format!("WIKI_{name}_MAX_TOKENS"))?;
+        }
+
+        override_from_env(
+            &mut self.resilience.failure_threshold,
+            "WIKI_CIRCUIT_FAILURE_THRESHOLD",
+        )?;
+        override_from_env(&mut self.resilience.open_secs, "WIKI_CIRCUIT_OPEN_SECS")?;
+
+        for (name, retry) in [
+            ("CHAT", &mut self.resilience.chat),
+            ("EMBED", &mut self.resilience.embedding),
+            ("QDRANT", &mut self.resilience.qdrant),
+            ("RERANK", &mut self.resilience.rerank),
+        ] {
+            override_from_env(
+                &mut retry.timeout_secs,
+                &format!("WIKI_{name}_TIMEOUT_SECS"),
+            )?;
+            override_from_env(
+                &mut retry.max_attempts,
+                &format!("WIKI_{name}_MAX_ATTEMPTS"),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Collects every problem so a misconfigured deployment is fixed in one pass.
+    fn validate(&self) -> Result<()> {
+        let mut errors = Vec::new();
+
+        for (key, env_key, value) in [
+            ("server.host", "WIKI_HOST", &self.server.host),
+            (
+                "server.api_key",
+                "WIKI_SERVICE_API_KEY",
+                &self.server.api_key,
+            ),
+            ("llm.url", "OLLAMA_URL", &self.llm.url),
+            ("llm.chat_model", "WIKI_CHAT_MODEL", &self.llm.chat_model),
+            ("llm.embed_model", "WIKI_EMBED_MODEL", &self.llm.embed_model),
+            ("qdrant.url", "QDRANT_URL", &self.qdrant.url),
+        ] {
+            if value.trim().is_empty() {
+                errors.push(format!("{key} is required (or set {env_key})"));
+            }
+        }
+
+        for (key, value) in [("llm.url", &self.llm.url), ("qdrant.url", &self.qdrant.url)] {
+            let is_http = value.starts_with("http://") || value.starts_with("https://");
+            if !value.trim().is_empty() && !is_http {
+                errors.push(format!("{key} must be an http(s) URL, got {value}"));
+            }
+        }
+
+        if self.server.port == 0 {
+            errors.push("server.port must be greater than 0".to_string());
+        }
+
+        if self.llm.embed_dimension == Some(0) {
+            errors.push("llm.embed_dimension must be greater than 0".to_string());
+        }
+
+        for (key, value) in [
+            ("llm.context_tokens", self.llm.context_tokens),
+            ("storage.retained_versions", self.storage.retained_versions),
+            ("jobs.workers", self.jobs.workers),
+            ("jobs.page_concurrency", self.jobs.page_concurrency),
+            ("indexing.batch_size", self.indexing.batch_size),
+            ("indexing.concurrency", self.indexing.concurrency),
+            (
+                "indexing.markdown_chunk_size",
+                self.indexing.markdown_chunk_size,
+            ),
+            ("indexing.code_chunk_size", self.indexing.code_chunk_size),
+            ("indexing.text_chunk_size", self.indexing.text_chunk_size),
+            (
+                "retrieval.max_chunks_per_file",
+                self.retrieval.max_chunks_per_file,
+            ),
+            ("retrieval.max_chunks", self.retrieval.max_chunks),
+            ("rerank.candidates", self.rerank.candidates),
+            ("sessions.history_tokens", self.sessions.history_tokens),
+        ] {
+            if value == 0 {
+                errors.push(format!("{key} must be greater than 0"));
+            }
+        }
+
+        for (key, value) in [
+            ("retrieval.vector_weight", self.retrieval.vector_weight),
+            ("retrieval.lexical_weight", self.retrieval.lexical_weight),
+        ] {
+            if !value.is_finite() || value < 0.0 {
+                errors.push(format!("{key} must be a non-negative number, got {value}"));
+            }
+        }
+
+        if !(0.0..=1.0).contains(&self.jobs.max_failed_page_ratio) {
+            errors.push(format!(
+                "jobs.max_failed_page_ratio must be between 0 and 1, got {}",
+                self.jobs.max_failed_page_ratio
+            ));
+        }
+
+        if self.retrieval.vector_weight == 0.0 && self.retrieval.lexical_weight == 0.0 {
+            errors.push(
+                "retrieval.vector_weight and retrieval.lexical_weight cannot both be 0".to_string(),
+            );
+        }
+
+        if self.rerank.mode != RerankMode::Off && self.rerank.candidates < self.retrieval.max_chunks
+        {
+            errors.push(format!(
+                "rerank.candidates ({}) must be at least retrieval.max_chunks ({})",
+                self.rerank.candidates, self.retrieval.max_chunks
+            ));
+        }
+
+        if self.rerank.mode == RerankMode::Endpoint && self.rerank.model.trim().is_empty() {
+            errors.push("rerank.model is required when rerank.mode is endpoint".to_string());
+        }
+
+        for (name, params) in [
+            ("planner", self.generation.planner),
+            ("writer", self.generation.writer),
+            ("answer", self.generation.answer),
+            ("chat", self.generation.chat),
+            ("rerank", self.generation.rerank),
+        ] {
+            if !(0.0..=2.0).contains(&params.temperature) {
+                errors.push(format!(
+                    "generation.{name}.temperature must be between 0 and 2, got {}",
+                    params.temperature
+                ));
+            }
+
+            if params.max_tokens == 0 {
+                errors.push(format!(
+                    "generation.{name}.max_tokens must be greater than 0"
+                ));
+            }
+        }
+
+        if self.resilience.failure_threshold == 0 {
+            errors.push("resilience.failure_threshold must be greater than 0".to_string());
+        }
+
+        for (name, retry) in [
+            ("chat", self.resilience.chat),
+            ("embedding", self.resilience.embedding),
+            ("qdrant", self.resilience.qdrant),
+            ("rerank", self.resilience.rerank),
+        ] {
+            if retry.timeout_secs == 0 {
+                errors.push(format!(
+                    "resilience.{name}.timeout_secs must be greater than 0"
+                ));
+            }
+
+            if retry.max_attempts == 0 {
+                errors.push(format!(
+                    "resilience.{name}.max_attempts must be greater than 0"
+                ));
+            }
+
+            if retry.base_delay_ms > retry.max_delay_ms {
+                errors.push(format!(
+                    "resilience.{name}.base_delay_ms cannot exceed max_delay_ms"
+                ));
+            }
+        }
+
+        if errors.is_empty() {
+            return Ok(());
+        }
+
+        Err(anyhow!(
+            "invalid configuration:\n  - {}",
+            errors.join("\n  - ")
+        ))
+    }
+}
+
+/// Replaces `${VAR}` and `${VAR:default}` with the environment value before
+/// parsing, so unquoted placeholders can also fill numeric settings. Values
+/// are escaped for TOML basic (double-quoted) strings; defaults are written
+/// in the file and pasted as they are. Comment lines are left untouched.
+fn interpolate(raw: &str) -> Result<String> {
+    let pattern = Regex::new(r"\$\{([A-Za-z_][A-Za-z0-9_]*)(?::([^}]*))?\}")?;
+    let mut missing = Vec::new();
+
+    let lines = raw
+        .lines()
+        .map(|line| {
+            if line.trim_start().starts_with('#') {
+                return line.to_string();
+            }
+
+            pattern
+                .replace_all(line, |captures: &Captures| {
+                    let name = &captures[1];
+                    match (env::var(name), captures.get(2)) {
+                        (Ok(value), _) => escape_toml_basic(&value),
+                        (Err(_), Some(default)) => default.as_str().to_string(),
+                        (Err(_), None) => {
+                            missing.push(name.to_string());
+                            String::new()
+                        }
+                    }
+                })
+                .into_owned()
         })
+        .collect::<Vec<_>>();
+
+    if !missing.is_empty() {
+        return Err(anyhow!(
+            "missing environment variables without a default: {}",
+            missing.join(", ")
+        ));
     }
+
+    Ok(lines.join("\n"))
 }
 
-fn get_required(key: &str) -> Result<String> {
-    env::var(key).with_context(|| format!("{key} env var is required"))
+fn escape_toml_basic(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for character in value.chars() {
+        match character {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            character if character.is_control() => {
+                escaped.push_str(&format!("\\u{:04X}", character as u32));
+            }
+            character => escaped.push(character),
+        }
+    }
+    escaped
+}
+
+fn override_from_env<T: FromStr>(target: &mut T, key: &str) -> Result<()>
+where
+    T::Err: Into<anyhow::Error>,
+{
+    if let Ok(value) = env::var(key) {
+        *target = value
+            .parse()
+            .map_err(Into::into)
+            .with_context(|| format!("{key} env var has an invalid value: {value}"))?;
+    }
+
+    Ok(())
+}
+
+fn override_optional_from_env<T: FromStr>(target: &mut Option<T>, key: &str) -> Result<()>
+where
+    T::Err: Into<anyhow::Error>,
+{
+    match env::var(key) {
+        Ok(value) if value.trim().is_empty() => *target = None,
+        Ok(value) => {
+            let parsed = value
+                .parse()
+                .map_err(Into::into)
+                .with_context(|| format!("{key} env var has an invalid value: {value}"))?;
+            *target = Some(parsed);
+        }
+        Err(_) => {}
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_variables_without_a_default_are_reported() {
+        let error =
+            interpolate("url = \"${WIKI_TEST_MISSING_URL}\"\nport = ${WIKI_TEST_MISSING_PORT}\n")
+                .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "missing environment variables without a default: WIKI_TEST_MISSING_URL, WIKI_TEST_MISSING_PORT"
+        );
+    }
+
+    #[test]
+    fn defaults_fill_unset_variables() {
+        let interpolated = interpolate(
+            "url = \"${WIKI_TEST_UNSET_URL:http://localhost:6333}\"\nkey = \"${WIKI_TEST_UNSET_KEY:}\"",
+        )
+        .unwrap();
+
+        assert_eq!(interpolated, "url = \"http://localhost:6333\"\nkey = \"\"");
+    }
+
+    #[test]
+    fn comment_lines_are_left_untouched() {
+        let raw = "# api_key = \"${WIKI_TEST_COMMENTED_KEY}\"\nport = 8080";
+
+        assert_eq!(interpolate(raw).unwrap(), raw);
+    }
+
+    #[test]
+    fn values_are_escaped_for_basic_strings() {
+        let value = "pa\"ss\\word\nnext\u{7}";
+        // SAFETY: no other test reads or writes this variable.
+        unsafe { env::set_var("WIKI_TEST_ESCAPED_KEY", value) };
+
+        let interpolated = interpolate("key = \"${WIKI_TEST_ESCAPED_KEY}\"").unwrap();
+        let parsed: toml::Table = toml::from_str(&interpolated).unwrap();
+
+        assert_eq!(parsed["key"].as_str(), Some(value));
+    }
 }
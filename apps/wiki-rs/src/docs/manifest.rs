@@ -0,0 +1,107 @@
+use super::RunInfo;
+use super::sections::SectionTree;
+use crate::models::{
+    Citation, PageGenerationError, PageManifest, RelatedPageLink, RetrievedChunk, WikiManifest,
+    WikiManifestPage, WikiPage, WikiStructure,
+};
+use anyhow::Result;
+use chrono::Utc;
+use std::collections::HashMap;
+use std::path::Path;
+use tokio::fs;
+
+pub(super) const WIKI_MANIFEST_FILE_NAME: &str = "wiki.json";
+
+/// Pages are listed in reading order; pages that failed to generate and had
+/// no previous version only appear in `structure` and `errors`. A page's
+/// section is the one the tree placed it in.
+pub fn build_wiki_manifest(
+    run: &RunInfo,
+    structure: &WikiStructure,
+    tree: &SectionTree,
+    written: &[(WikiPage, PageManifest)],
+    errors: &[PageGenerationError],
+) -> WikiManifest {
+    let titles = structure
+        .pages
+        .iter()
+        .map(|page| (page.id.as_str(), page.title.as_str()))
+        .collect::<HashMap<_, _>>();
+    let files = written
+        .iter()
+        .map(|(page, entry)| (page.id.as_str(), entry.file_name.as_str()))
+        .collect::<HashMap<_, _>>();
+    let placements = tree
+        .flatten()
+        .into_iter()
+        .flat_map(|(node, _)| {
+            node.pages
+                .iter()
+                .map(move |page_id| (page_id.as_str(), node.id.as_str()))
+        })
+        .collect::<HashMap<_, _>>();
+
+    let pages = written
+        .iter()
+        .map(|(page, entry)| WikiManifestPage {
+            id: page.id.clone(),
+            title: page.title.clone(),
+            description: page.description.clone(),
+            importance: page.importance.clone(),
+            section_id: placements
+                .get(page.id.as_str())
+                .map(|section_id| section_id.to_string()),
+            file_name: entry.file_name.clone(),
+            related_pages: page
+                .related_pages
+                .iter()
+                .map(|related| RelatedPageLink {
+                    id: related.clone(),
+                    title: titles.get(related.as_str()).map(|title| title.to_string()),
+                    file_name: files.get(related.as_str()).map(|file| file.to_string()),
+                })
+                .collect(),
+            citations: entry.citations.clone(),
+            generated_at: entry.generated_at,
+        })
+        .collect();
+
+    WikiManifest {
+        repo_id: run.repo_id,
+        version: run.version.clone(),
+        commit_sha: run.commit_sha.clone(),
+        title: structure.title.clone(),
+        description: structure.description.clone(),
+        index_file: "README.md".to_string(),
+        models: run.models.clone(),
+        started_at: run.started_at,
+        generated_at: Utc::now(),
+        planner_inputs: run.planner_inputs.clone(),
+        structure: structure.clone(),
+        sections: tree.manifest_sections(),
+        pages,
+        errors: errors.to_vec(),
+    }
+}
+
+/// Sources handed to the writer for a page, numbered in retrieval order.
+pub fn page_citations(chunks: &[RetrievedChunk]) -> Vec<Citation> {
+    chunks
+        .iter()
+        .enumerate()
+        .map(|(index, chunk)| Citation {
+            index: index + 1,
+            source: chunk.source.clone(),
+            start_line: chunk.start_line,
+            end_line: chunk.end_line,
+            symbol: chunk.symbol.as_ref().map(|symbol| symbol.name.clone()),
+        })
+        .collect()
+}
+
+pub async fn write_wiki_manifest(output_dir: &Path, manifest: &WikiManifest) -> Result<()> {
+    let serialized = serde_json::to_vec_pretty(manifest)?;
+    fs::write(output_dir.join(WIKI_MANIFEST_FILE_NAME), serialized).await?;
+
+    Ok(())
+}
@@ -0,0 +1,582 @@
+mod manifest;
+mod sections;
+mod site;
+mod versions;
+
+use crate::agents::writer::{WriterAgent, WriterInput};
+use crate::budget::PromptBudget;
+use crate::jobs::JobHandle;
+use crate::models::{
+    GenerationManifest, JobPhase, ManifestChunk, PageGenerationError, PageManifest, PromptTrim,
+    RetrievedChunk, WikiModels, WikiPage, WikiSection, WikiStructure,
+};
+use crate::rag::RagEngine;
+use crate::storage::write_atomic;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use futures::{StreamExt, stream};
+use sha2::{Digest, Sha256};
+use slug::slugify;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tracing::warn;
+use uuid::Uuid;
+
+use manifest::{build_wiki_manifest, page_citations, write_wiki_manifest};
+use sections::{SectionTree, write_index_files};
+pub use site::SiteExporter;
+pub use versions::WikiVersions;
+
+const MANIFEST_FILE_NAME: &str = ".generation-manifest.json";
+
+#[derive(Debug)]
+pub struct GeneratedDocsResult {
+    pub generated_pages: usize,
+    pub reused_pages: usize,
+    /// Failed pages whose previous version was published again.
+    pub carried_over_pages: usize,
+    pub prompt_trims: Vec<PromptTrim>,
+    pub errors: Vec<PageGenerationError>,
+}
+
+#[derive(Clone)]
+pub struct DocumentationGenerator {
+    writer: WriterAgent,
+    rag: RagEngine,
+    budget: PromptBudget,
+    page_concurrency: usize,
+}
+
+impl DocumentationGenerator {
+    pub fn new(
+        writer: WriterAgent,
+        rag: RagEngine,
+        budget: PromptBudget,
+        page_concurrency: usize,
+    ) -> Self {
+        Self {
+            writer,
+            rag,
+            budget,
+            page_concurrency,
+        }
+    }
+
+    /// Reads the manifest of the wiki published in `dir`, if there is a usable one.
+    pub async fn load_previous(&self, dir: &Path) -> Option<PreviousWiki> {
+        let raw = fs::read(dir.join(MANIFEST_FILE_NAME)).await.ok()?;
+
+        match serde_json::from_slice(&raw) {
+            Ok(manifest) => Some(PreviousWiki {
+                dir: dir.to_path_buf(),
+                manifest,
+            }),
+            Err(error) => {
+                warn!(dir = %dir.display(), "ignoring unreadable generation manifest: {error}");
+                None
+            }
+        }
+    }
+
+    /// Writes every page of `planner_output` into `output_dir`, copying pages
+    /// from `previous` whose retrieved context hashes the same as before, then
+    /// the `README.md` index and the `wiki.json` manifest.
+    pub async fn generate(
+        &self,
+        output_dir: &Path,
+        run: RunInfo,
+        planner_output: WikiStructure,
+        previous: Option<PreviousWiki>,
+        job: &JobHandle,
+    ) -> Result<GeneratedDocsResult> {
+        fs::create_dir_all(output_dir).await?;
+
+        let section_map = planner_output
+            .sections
+            .iter()
+            .map(|section| (section.id.clone(), section.clone()))
+            .collect::<HashMap<_, _>>();
+
+        let page_map = planner_output
+            .pages
+            .iter()
+            .map(|page| (page.id.clone(), page.clone()))
+            .collect::<HashMap<_, _>>();
+
+        let tree = SectionTree::build(&planner_output);
+        let ordered_pages = tree
+            .pages
+            .iter()
+            .filter_map(|placed| {
+                let page = page_map.get(&placed.id)?;
+                Some((page.clone(), placed.file_name.clone()))
+            })
+            .collect::<Vec<_>>();
+        let total = ordered_pages.len();
+        let context = PageContext {
+            output_dir,
+            repo_id: run.repo_id,
+            section_map: &section_map,
+            project_overview: format!("{}\n\n{}", planner_output.title, planner_output.description),
+            project_features: tree.features(),
+            writer_hash: content_hash(&self.writer.fingerprint()?),
+            previous_dir: previous.as_ref().map(|previous| previous.dir.clone()),
+            previous_pages: previous
+                .map(|previous| previous.manifest.pages)
+                .unwrap_or_default(),
+        };
+
+        job.set_phase(JobPhase::Writing { page: 0, total }).await;
+
+        // File names are fixed by each page's position in the section tree,
+        // so the numbering stays deterministic whatever order the pages
+        // finish in.
+        let mut pending = stream::iter(ordered_pages.into_iter().enumerate())
+            .map(|(index, (page, file_name))| {
+                let context = &context;
+                async move {
+                    let outcome = self.write_page(context, &page, file_name.clone()).await;
+                    (index, page, file_name, outcome)
+                }
+            })
+            .buffer_unordered(self.page_concurrency.max(1));
+
+        let mut settled = Vec::with_capacity(total);
+        while let Some((index, page, file_name, outcome)) = pending.next().await {
+            let outcome = match outcome {
+                Ok(written) => Ok(written),
+                Err(error) => {
+                    let page_error = PageGenerationError {
+                        page_id: page.id.clone(),
+                        page_title: page.title.clone(),
+                        error: error.to_string(),
+                    };
+
+                    job.record_page_error(page_error.clone()).await;
+                    Err(page_error)
+                }
+            };
+
+            settled.push((index, page, file_name, outcome));
+            job.set_phase(JobPhase::Writing {
+                page: settled.len(),
+                total,
+            })
+            .await;
+        }
+        drop(pending);
+
+        settled.sort_by_key(|(index, _, _, _)| *index);
+
+        let mut written_pages = Vec::new();
+        let mut reused_pages = 0;
+        let mut carried_over_pages = 0;
+        let mut prompt_trims = Vec::new();
+        let mut errors = Vec::new();
+        for (_, page, file_name, outcome) in settled {
+            match outcome {
+                Ok(written) => {
+                    if written.reused {
+                        reused_pages += 1;
+                    }
+                    prompt_trims.extend(written.trim);
+                    written_pages.push((page, written.entry));
+                }
+                Err(page_error) => {
+                    if let Some(entry) = context.carry_over(&page.id, &file_name).await {
+                        carried_over_pages += 1;
+                        written_pages.push((page, entry));
+                    }
+                    errors.push(page_error);
+                }
+            }
+        }
+
+        let generated_files = written_pages
+            .iter()
+            .map(|(page, entry)| (page.id.clone(), entry.file_name.clone()))
+            .collect::<HashMap<_, _>>();
+        write_index_files(output_dir, &planner_output, &tree, &generated_files).await?;
+
+        let wiki_manifest =
+            build_wiki_manifest(&run, &planner_output, &tree, &written_pages, &errors);
+        write_wiki_manifest(output_dir, &wiki_manifest).await?;
+
+        let manifest = GenerationManifest {
+            tree_hash: run.tree_hash,
+            structure: planner_output,
+            planner_inputs: run.planner_inputs,
+            pages: written_pages
+                .into_iter()
+                .map(|(page, entry)| (page.id, entry))
+                .collect(),
+            generated_at: Utc::now(),
+        };
+        write_manifest(output_dir, &manifest).await?;
+
+        Ok(GeneratedDocsResult {
+            generated_pages: generated_files.len() - carried_over_pages,
+            reused_pages,
+            carried_over_pages,
+            prompt_trims,
+            errors,
+        })
+    }
+
+    async fn write_page(
+        &self,
+        context: &PageContext<'_>,
+        page: &WikiPage,
+        file_name: String,
+    ) -> Result<WrittenPage> {
+        let section_title = resolve_section_title(page, context.section_map);
+        let context_chunks = self
+            .rag
+            .retrieve_page_context(context.repo_id, &section_title, page)
+            .await?;
+
+        let mut input = WriterInput {
+            project_overview: context.project_overview.clone(),
+            project_features: context.project_features.clone(),
+            page_title: page.title.clone(),
+            page_description: page.description.clone(),
+            section_title: section_title.clone(),
+            relevant_sources: String::new(),
+            files_content: String::new(),
+        };
+        let available = self.writer.sources_budget(&input)?;
+        let (context_chunks, trim) = self.fit_chunks(page, context_chunks, available);
+
+        let entry = PageManifest {
+            context_hash: page_context_hash(
+                page,
+                &section_title,
+                &context_chunks,
+                &context.writer_hash,
+            ),
+            chunks: context_chunks
+                .iter()
+                .map(|chunk| ManifestChunk {
+                    id: chunk.id.clone(),
+                    hash: content_hash(&chunk.content),
+                })
+                .collect(),
+            citations: page_citations(&context_chunks),
+            generated_at: Utc::now(),
+            file_name,
+        };
+        let output_path = context.output_dir.join(&entry.file_name);
+        if let Some(parent) = output_path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        if let Some((markdown, generated_at)) = context.reusable_markdown(&page.id, &entry).await {
+            fs::write(&output_path, markdown).await?;
+
+            return Ok(WrittenPage {
+                entry: PageManifest {
+                    generated_at,
+                    ..entry
+                },
+                reused: true,
+                trim: None,
+            });
+        }
+
+        input.relevant_sources = build_relevant_sources(&context_chunks);
+        input.files_content = build_files_content(&context_chunks);
+
+        let markdown = self.writer.run(input).await?;
+        fs::write(&output_path, markdown).await?;
+
+        Ok(WrittenPage {
+            entry,
+            reused: false,
+            trim,
+        })
+    }
+
+    /// Keeps the best-ranked chunks that fit in `available` tokens. A chunk
+    /// too large for the remaining space is skipped, not cut, so a smaller
+    /// one further down can still make it in.
+    fn fit_chunks(
+        &self,
+        page: &WikiPage,
+        chunks: Vec<RetrievedChunk>,
+        available: usize,
+    ) -> (Vec<RetrievedChunk>, Option<PromptTrim>) {
+        let mut used = 0;
+        let mut original = 0;
+        let mut kept = Vec::new();
+        let mut dropped = Vec::new();
+
+        for chunk in chunks {
+            let single = std::slice::from_ref(&chunk);
+            let cost = self.budget.count(&build_files_content(single))
+                + self.budget.count(&build_relevant_sources(single));
+            original += cost;
+
+            if used + cost <= available {
+                used += cost;
+                kept.push(chunk);
+            } else {
+                dropped.push(match (chunk.start_line, chunk.end_line) {
+                    (Some(start), Some(end)) => format!("{}#L{start}-L{end}", chunk.source),
+                    _ => chunk.source.clone(),
+                });
+            }
+        }
+
+        if dropped.is_empty() {
+            return (kept, None);
+        }
+
+        let trim = PromptTrim {
+            prompt: "writer".to_string(),
+            page_id: Some(page.id.clone()),
+            section: "sources".to_string(),
+            original_tokens: original,
+            kept_tokens: used,
+            detail: format!(
+                "dropped {} of {} chunks: {}",
+                dropped.len(),
+                kept.len() + dropped.len(),
+                dropped.join(", ")
+            ),
+        };
+        (kept, Some(trim))
+    }
+}
+
+/// Inputs shared by every page of one generation run.
+struct PageContext<'a> {
+    output_dir: &'a Path,
+    repo_id: Uuid,
+    section_map: &'a HashMap<String, WikiSection>,
+    project_overview: String,
+    project_features: String,
+    writer_hash: String,
+    previous_dir: Option<PathBuf>,
+    previous_pages: HashMap<String, PageManifest>,
+}
+
+impl PageContext<'_> {
+    /// The previously published markdown of a page whose context is unchanged,
+    /// with the time it was written.
+    async fn reusable_markdown(
+        &self,
+        page_id: &str,
+        entry: &PageManifest,
+    ) -> Option<(String, DateTime<Utc>)> {
+        let previous = self.previous_pages.get(page_id)?;
+        if previous.context_hash != entry.context_hash {
+            return None;
+        }
+
+        let dir = self.previous_dir.as_ref()?;
+        let markdown = fs::read_to_string(dir.join(&previous.file_name))
+            .await
+            .ok()?;
+        Some((markdown, previous.generated_at))
+    }
+
+    /// Publishes the previous version of a page that failed this run under
+    /// its new file name. Its manifest entry keeps the old context hash, so
+    /// the next run writes the page again.
+    async fn carry_over(&self, page_id: &str, file_name: &str) -> Option<PageManifest> {
+        let previous = self.previous_pages.get(page_id)?;
+        let dir = self.previous_dir.as_ref()?;
+        let markdown = fs::read_to_string(dir.join(&previous.file_name))
+            .await
+            .ok()?;
+
+        let output_path = self.output_dir.join(file_name);
+        if let Some(parent) = output_path.parent() {
+            fs::create_dir_all(parent).await.ok()?;
+        }
+        fs::write(&output_path, markdown).await.ok()?;
+
+        Some(PageManifest {
+            file_name: file_name.to_string(),
+            ..previous.clone()
+        })
+    }
+}
+
+/// Identifies one generation run in the manifests it writes.
+pub struct RunInfo {
+    pub repo_id: Uuid,
+    pub version: String,
+    pub commit_sha: Option<String>,
+    pub tree_hash: String,
+    pub planner_inputs: Vec<String>,
+    pub models: WikiModels,
+    pub started_at: DateTime<Utc>,
+}
+
+/// The wiki published by an earlier run, used as the baseline for reuse.
+pub struct PreviousWiki {
+    pub dir: PathBuf,
+    pub manifest: GenerationManifest,
+}
+
+struct WrittenPage {
+    entry: PageManifest,
+    reused: bool,
+    trim: Option<PromptTrim>,
+}
+
+/// Hashes the sorted list of file paths so reordering or whitespace in the
+/// tree listing does not count as a structural change.
+pub fn tree_fingerprint(repo_tree: &str) -> String {
+    let mut lines = repo_tree
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>();
+    lines.sort_unstable();
+
+    content_hash(&lines.join("\n"))
+}
+
+/// Chunks are hashed in id order: reranking may shuffle an otherwise identical context.
+fn page_context_hash(
+    page: &WikiPage,
+    section_title: &str,
+    chunks: &[RetrievedChunk],
+    writer_hash: &str,
+) -> String {
+    let mut chunk_keys = chunks
+        .iter()
+        .map(|chunk| format!("{}:{}", chunk.id, content_hash(&chunk.content)))
+        .collect::<Vec<_>>();
+    chunk_keys.sort_unstable();
+
+    content_hash(&format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        writer_hash,
+        page.id,
+        page.title,
+        page.description,
+        section_title,
+        chunk_keys.join("\n")
+    ))
+}
+
+fn content_hash(content: &str) -> String {
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
+async fn write_manifest(output_dir: &Path, manifest: &GenerationManifest) -> Result<()> {
+    write_atomic(
+        &output_dir.join(MANIFEST_FILE_NAME),
+        serde_json::to_vec_pretty(manifest)?,
+    )
+    .await
+}
+
+fn resolve_section_title(page: &WikiPage, section_map: &HashMap<String, WikiSection>) -> String {
+    let Some(parent) = &page.parent_section else {
+        return "General".to_string();
+    };
+
+    section_map
+        .get(parent)
+        .map(|section| section.title.clone())
+        .unwrap_or_else(|| "General".to_string())
+}
+
+fn build_relevant_sources(chunks: &[RetrievedChunk]) -> String {
+    if chunks.is_empty() {
+        return "- No se recuperaron fuentes".to_string();
+    }
+
+    chunks
+        .iter()
+        .map(|chunk| {
+            let line_range = match (chunk.start_line, chunk.end_line) {
+                (Some(start), Some(end)) => format!("#L{start}-L{end}"),
+                _ => String::new(),
+            };
+
+            let symbol = chunk
+                .symbol
+                .as_ref()
+                .map(|symbol| format!(" `{} {}`", symbol.kind, symbol.name))
+                .unwrap_or_default();
+
+            format!(
+                "- {}{}{} (score={:.4}{})",
+                chunk.source,
+                line_range,
+                symbol,
+                chunk.score,
+                format_rerank_score(chunk)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn build_files_content(chunks: &[RetrievedChunk]) -> String {
+    if chunks.is_empty() {
+        return "[SOURCE: none]\nNo hay contexto recuperado para esta pagina.\n[/SOURCE]"
+            .to_string();
+    }
+
+    chunks
+        .iter()
+        .map(|chunk| {
+            let line_range = match (chunk.start_line, chunk.end_line) {
+                (Some(start), Some(end)) => format!("{start}-{end}"),
+                _ => "n/a".to_string(),
+            };
+
+            let symbol = chunk
+                .symbol
+                .as_ref()
+                .map(|symbol| {
+                    format!(
+                        " | symbol={} {} | signature={}",
+                        symbol.kind, symbol.name, symbol.signature
+                    )
+                })
+                .unwrap_or_default();
+
+            [
+                format!(
+                    "[SOURCE: {} | chunk={} | kind={} | language={} | lines={}{} | score={:.4}{}]",
+                    chunk.source,
+                    chunk.chunk_index,
+                    chunk.kind,
+                    chunk.language,
+                    line_range,
+                    symbol,
+                    chunk.score,
+                    format_rerank_score(chunk)
+                ),
+                chunk.content.clone(),
+                "[/SOURCE]".to_string(),
+            ]
+            .join("\n")
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+fn format_rerank_score(chunk: &RetrievedChunk) -> String {
+    chunk
+        .rerank_score
+        .map(|score| format!(", rerank={score:.4}"))
+        .unwrap_or_default()
+}
+
+fn to_slug(value: &str) -> String {
+    let slugged = slugify(value);
+    if slugged.is_empty() {
+        "documentacion".to_string()
+    } else {
+        slugged
+    }
+}
@@ -0,0 +1,548 @@
+use super::to_slug;
+use crate::models::{WikiManifestSection, WikiPage, WikiSection, WikiStructure};
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use tokio::fs;
+
+const INDEX_FILE_NAME: &str = "README.md";
+
+/// A section placed in the wiki, with its number and directory.
+pub struct SectionNode {
+    pub id: String,
+    pub title: String,
+    /// Position in the tree, e.g. `2.1`.
+    pub number: String,
+    /// Relative to the wiki root, e.g. `02-arquitectura/03-backend`.
+    pub dir: String,
+    pub pages: Vec<String>,
+    pub children: Vec<SectionNode>,
+}
+
+/// Where a page is written, relative to the wiki root.
+pub struct PlacedPage {
+    pub id: String,
+    pub file_name: String,
+}
+
+/// The sections of a `WikiStructure` nested through `subsections`. Each
+/// section gets a directory; its pages and subsections are numbered in one
+/// sequence inside it, pages first, so listing a directory shows them in
+/// reading order.
+pub struct SectionTree {
+    pub roots: Vec<SectionNode>,
+    /// Every page in reading order; pages no section lists come last, at the
+    /// wiki root.
+    pub pages: Vec<PlacedPage>,
+}
+
+struct Builder<'a> {
+    sections: HashMap<&'a str, &'a WikiSection>,
+    titles: HashMap<&'a str, &'a str>,
+    visited: HashSet<&'a str>,
+    placed: HashSet<&'a str>,
+    pages: Vec<PlacedPage>,
+}
+
+impl SectionNode {
+    pub fn label(&self) -> String {
+        format!("{}. {}", self.number, self.title)
+    }
+}
+
+impl SectionTree {
+    /// Sections no other section claims are roots. A section claimed twice
+    /// stays under the first claimant, and a page listed twice under the
+    /// first section reached; sections only reachable through a cycle become
+    /// roots.
+    pub fn build(structure: &WikiStructure) -> Self {
+        let mut sections = HashMap::new();
+        for section in &structure.sections {
+            sections.entry(section.id.as_str()).or_insert(section);
+        }
+
+        let mut titles = HashMap::new();
+        for page in &structure.pages {
+            titles
+                .entry(page.id.as_str())
+                .or_insert(page.title.as_str());
+        }
+
+        let claimed = structure
+            .sections
+            .iter()
+            .flat_map(|section| {
+                section
+                    .subsections
+                    .iter()
+                    .filter(move |id| **id != section.id)
+            })
+            .map(String::as_str)
+            .collect::<HashSet<_>>();
+
+        let mut builder = Builder {
+            sections,
+            titles,
+            visited: HashSet::new(),
+            placed: HashSet::new(),
+            pages: Vec::new(),
+        };
+
+        let mut roots = Vec::new();
+        let candidates = structure
+            .sections
+            .iter()
+            .filter(|section| !claimed.contains(section.id.as_str()))
+            .chain(structure.sections.iter());
+        for section in candidates {
+            if !builder.visited.insert(section.id.as_str()) {
+                continue;
+            }
+
+            let position = roots.len() + 1;
+            roots.push(builder.node(section, "", "", position, position));
+        }
+
+        let loose = structure
+            .pages
+            .iter()
+            .filter(|page| builder.placed.insert(page.id.as_str()))
+            .collect::<Vec<_>>();
+        for (index, page) in loose.into_iter().enumerate() {
+            builder.pages.push(PlacedPage {
+                id: page.id.clone(),
+                file_name: page_file_name(roots.len() + index + 1, &page.title),
+            });
+        }
+
+        Self {
+            roots,
+            pages: builder.pages,
+        }
+    }
+
+    /// Every section, parents before their children, with its parent.
+    pub fn flatten(&self) -> Vec<(&SectionNode, Option<&SectionNode>)> {
+        fn walk<'a>(
+            node: &'a SectionNode,
+            parent: Option<&'a SectionNode>,
+            out: &mut Vec<(&'a SectionNode, Option<&'a SectionNode>)>,
+        ) {
+            out.push((node, parent));
+            for child in &node.children {
+                walk(child, Some(node), out);
+            }
+        }
+
+        let mut out = Vec::new();
+        for root in &self.roots {
+            walk(root, None, &mut out);
+        }
+        out
+    }
+
+    /// Sections as the writer sees them: one line each, indented by depth.
+    pub fn features(&self) -> String {
+        if self.roots.is_empty() {
+            return "- Sin secciones detectadas".to_string();
+        }
+
+        self.flatten()
+            .into_iter()
+            .map(|(node, _)| {
+                let depth = node.number.matches('.').count();
+                format!("{}- {}", "  ".repeat(depth), node.label())
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Sections for `wiki.json`, in the same order as `flatten`.
+    pub fn manifest_sections(&self) -> Vec<WikiManifestSection> {
+        self.flatten()
+            .into_iter()
+            .map(|(node, parent)| WikiManifestSection {
+                id: node.id.clone(),
+                number: node.number.clone(),
+                title: node.title.clone(),
+                index_file: index_file(&node.dir),
+                parent: parent.map(|parent| parent.id.clone()),
+                pages: node.pages.clone(),
+                subsections: node.children.iter().map(|child| child.id.clone()).collect(),
+            })
+            .collect()
+    }
+}
+
+impl<'a> Builder<'a> {
+    fn node(
+        &mut self,
+        section: &'a WikiSection,
+        parent_number: &str,
+        parent_dir: &str,
+        ordinal: usize,
+        position: usize,
+    ) -> SectionNode {
+        let number = if parent_number.is_empty() {
+            ordinal.to_string()
+        } else {
+            format!("{parent_number}.{ordinal}")
+        };
+        let dir = join(
+            parent_dir,
+            &format!("{position:02}-{}", to_slug(&section.title)),
+        );
+
+        let mut pages = Vec::new();
+        for page_id in &section.pages {
+            let Some(title) = self.titles.get(page_id.as_str()).copied() else {
+                continue;
+            };
+            if !self.placed.insert(page_id.as_str()) {
+                continue;
+            }
+
+            pages.push(page_id.clone());
+            self.pages.push(PlacedPage {
+                id: page_id.clone(),
+                file_name: join(&dir, &page_file_name(pages.len(), title)),
+            });
+        }
+
+        let mut children = Vec::new();
+        for child_id in &section.subsections {
+            let Some(child) = self.sections.get(child_id.as_str()).copied() else {
+                continue;
+            };
+            if !self.visited.insert(child.id.as_str()) {
+                continue;
+            }
+
+            let ordinal = children.len() + 1;
+            let position = pages.len() + ordinal;
+            children.push(self.node(child, &number, &dir, ordinal, position));
+        }
+
+        SectionNode {
+            id: section.id.clone(),
+            title: section.title.clone(),
+            number,
+            dir,
+            pages,
+            children,
+        }
+    }
+}
+
+/// Writes `README.md` at the wiki root, with the whole tree as nested
+/// headings, and one `README.md` per section directory listing its pages
+/// and subsections. Pages that failed to generate are left out.
+pub async fn write_index_files(
+    output_dir: &Path,
+    structure: &WikiStructure,
+    tree: &SectionTree,
+    generated_files: &HashMap<String, String>,
+) -> Result<()> {
+    let pages = structure
+        .pages
+        .iter()
+        .map(|page| (page.id.as_str(), page))
+        .collect::<HashMap<_, _>>();
+    let links = |node: &SectionNode, base: &str| {
+        node.pages
+            .iter()
+            .filter_map(|page_id| {
+                let page = pages.get(page_id.as_str())?;
+                let file_name = generated_files.get(page_id)?;
+                Some(page_link(page, relative_to(file_name, base)))
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let mut lines = vec![
+        format!("# {}", structure.title),
+        String::new(),
+        structure.description.clone(),
+        String::new(),
+        "## Estructura de documentacion".to_string(),
+        String::new(),
+    ];
+
+    for (node, _) in tree.flatten() {
+        let level = (3 + node.number.matches('.').count()).min(6);
+        lines.push(format!(
+            "{} [{}](./{})",
+            "#".repeat(level),
+            node.label(),
+            index_file(&node.dir)
+        ));
+        lines.extend(links(node, ""));
+        lines.push(String::new());
+    }
+
+    let loose = tree
+        .pages
+        .iter()
+        .filter(|placed| !placed.file_name.contains('/'))
+        .filter_map(|placed| {
+            let page = pages.get(placed.id.as_str())?;
+            let file_name = generated_files.get(&placed.id)?;
+            Some(page_link(page, file_name))
+        })
+        .collect::<Vec<_>>();
+    if !loose.is_empty() {
+        lines.push("### Otras paginas".to_string());
+        lines.extend(loose);
+    }
+
+    write_index(output_dir, &lines).await?;
+
+    for (node, parent) in tree.flatten() {
+        let mut lines = vec![format!("# {}", node.label()), String::new()];
+        lines.push(match parent {
+            Some(parent) => format!(
+                "Parte de [{}](../{INDEX_FILE_NAME}) · [Inicio]({}{INDEX_FILE_NAME})",
+                parent.label(),
+                "../".repeat(node.dir.matches('/').count() + 1)
+            ),
+            None => format!("[Inicio](../{INDEX_FILE_NAME})"),
+        });
+        lines.push(String::new());
+
+        let page_links = links(node, &node.dir);
+        if !page_links.is_empty() {
+            lines.push("## Paginas".to_string());
+            lines.extend(page_links);
+            lines.push(String::new());
+        }
+
+        if !node.children.is_empty() {
+            lines.push("## Subsecciones".to_string());
+            for child in &node.children {
+                lines.push(format!(
+                    "- [{}](./{})",
+                    child.label(),
+                    relative_to(&index_file(&child.dir), &node.dir)
+                ));
+            }
+        }
+
+        write_index(&output_dir.join(&node.dir), &lines).await?;
+    }
+
+    Ok(())
+}
+
+async fn write_index(dir: &Path, lines: &[String]) -> Result<()> {
+    fs::create_dir_all(dir).await?;
+    let content = format!("{}\n", lines.join("\n").trim());
+    fs::write(dir.join(INDEX_FILE_NAME), content).await?;
+
+    Ok(())
+}
+
+fn page_link(page: &WikiPage, file_name: &str) -> String {
+    format!("- [{}](./{file_name})", page.title)
+}
+
+fn page_file_name(position: usize, title: &str) -> String {
+    format!("{position:02}-{}.md", to_slug(title))
+}
+
+fn index_file(dir: &str) -> String {
+    join(dir, INDEX_FILE_NAME)
+}
+
+fn join(dir: &str, name: &str) -> String {
+    if dir.is_empty() {
+        name.to_string()
+    } else {
+        format!("{dir}/{name}")
+    }
+}
+
+/// `path` relative to the directory `base`, which must contain it.
+fn relative_to<'a>(path: &'a str, base: &str) -> &'a str {
+    if base.is_empty() {
+        return path;
+    }
+
+    path.strip_prefix(base)
+        .and_then(|rest| rest.strip_prefix('/'))
+        .unwrap_or(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Importance;
+
+    fn page(id: &str) -> WikiPage {
+        WikiPage {
+            id: id.to_string(),
+            title: id.to_string(),
+            description: String::new(),
+            importance: Importance::Medium,
+            relevant_files: Vec::new(),
+            related_pages: Vec::new(),
+            parent_section: None,
+        }
+    }
+
+    fn section(id: &str, pages: &[&str], subsections: &[&str]) -> WikiSection {
+        WikiSection {
+            id: id.to_string(),
+            title: id.to_string(),
+            pages: pages.iter().map(|page| page.to_string()).collect(),
+            subsections: subsections.iter().map(|id| id.to_string()).collect(),
+        }
+    }
+
+    fn build(sections: Vec<WikiSection>, pages: &[&str]) -> SectionTree {
+        SectionTree::build(&WikiStructure {
+            title: "Wiki".to_string(),
+            description: String::new(),
+            sections,
+            pages: pages.iter().map(|id| page(id)).collect(),
+        })
+    }
+
+    fn outline(tree: &SectionTree) -> Vec<(String, String, Option<String>)> {
+        tree.flatten()
+            .into_iter()
+            .map(|(node, parent)| {
+                (
+                    node.number.clone(),
+                    node.dir.clone(),
+                    parent.map(|parent| parent.id.clone()),
+                )
+            })
+            .collect()
+    }
+
+    fn file_names(tree: &SectionTree) -> Vec<(&str, &str)> {
+        tree.pages
+            .iter()
+            .map(|placed| (placed.id.as_str(), placed.file_name.as_str()))
+            .collect()
+    }
+
+    fn entry(number: &str, dir: &str, parent: Option<&str>) -> (String, String, Option<String>) {
+        (
+            number.to_string(),
+            dir.to_string(),
+            parent.map(str::to_string),
+        )
+    }
+
+    #[test]
+    fn sections_are_numbered_after_their_pages() {
+        let tree = build(
+            vec![
+                section("guia", &["inicio", "uso"], &["api"]),
+                section("api", &["rest"], &[]),
+                section("anexos", &["glosario"], &[]),
+            ],
+            &["inicio", "uso", "rest", "glosario", "suelta"],
+        );
+
+        assert_eq!(
+            outline(&tree),
+            vec![
+                entry("1", "01-guia", None),
+                entry("1.1", "01-guia/03-api", Some("guia")),
+                entry("2", "02-anexos", None),
+            ]
+        );
+        assert_eq!(
+            file_names(&tree),
+            vec![
+                ("inicio", "01-guia/01-inicio.md"),
+                ("uso", "01-guia/02-uso.md"),
+                ("rest", "01-guia/03-api/01-rest.md"),
+                ("glosario", "02-anexos/01-glosario.md"),
+                ("suelta", "03-suelta.md"),
+            ]
+        );
+    }
+
+    #[test]
+    fn sections_only_reachable_through_a_cycle_become_roots() {
+        let tree = build(
+            vec![
+                section("a", &["uno"], &["b"]),
+                section("b", &["dos"], &["a"]),
+                section("c", &["tres"], &["c"]),
+            ],
+            &["uno", "dos", "tres"],
+        );
+
+        assert_eq!(
+            outline(&tree),
+            vec![
+                entry("1", "01-c", None),
+                entry("2", "02-a", None),
+                entry("2.1", "02-a/02-b", Some("a")),
+            ]
+        );
+        assert!(tree.roots[0].children.is_empty());
+    }
+
+    #[test]
+    fn repeated_claims_keep_the_first() {
+        let tree = build(
+            vec![
+                section("a", &["uno"], &["c"]),
+                section("b", &["uno", "dos"], &["c"]),
+                section("c", &["tres"], &[]),
+            ],
+            &["uno", "dos", "tres"],
+        );
+
+        assert_eq!(
+            outline(&tree),
+            vec![
+                entry("1", "01-a", None),
+                entry("1.1", "01-a/02-c", Some("a")),
+                entry("2", "02-b", None),
+            ]
+        );
+        assert!(tree.roots[1].children.is_empty());
+        assert_eq!(tree.roots[1].pages, vec!["dos"]);
+        assert_eq!(
+            file_names(&tree),
+            vec![
+                ("uno", "01-a/01-uno.md"),
+                ("tres", "01-a/02-c/01-tres.md"),
+                ("dos", "02-b/01-dos.md"),
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_pages_and_subsections_are_skipped() {
+        let tree = build(vec![section("a", &["falta", "uno"], &["nada"])], &["uno"]);
+
+        assert_eq!(tree.roots[0].pages, vec!["uno"]);
+        assert!(tree.roots[0].children.is_empty());
+        assert_eq!(file_names(&tree), vec![("uno", "01-a/01-uno.md")]);
+    }
+
+    #[test]
+    fn manifest_sections_link_parents_and_index_files() {
+        let tree = build(
+            vec![section("a", &["uno"], &["b"]), section("b", &["dos"], &[])],
+            &["uno", "dos"],
+        );
+
+        let sections = tree.manifest_sections();
+        assert_eq!(sections[0].index_file, "01-a/README.md");
+        assert_eq!(sections[0].subsections, vec!["b"]);
+        assert_eq!(sections[1].index_file, "01-a/02-b/README.md");
+        assert_eq!(sections[1].parent.as_deref(), Some("a"));
+        assert_eq!(
+            relative_to(&sections[1].index_file, "01-a"),
+            "02-b/README.md"
+        );
+    }
+}
@@ -0,0 +1,563 @@
+use super::manifest::WIKI_MANIFEST_FILE_NAME;
+use crate::models::{WikiManifest, WikiManifestPage};
+use anyhow::{Context, Result, bail};
+use pulldown_cmark::{CodeBlockKind, CowStr, Event, Options, Parser, Tag, TagEnd, html};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+const SITE_ASSETS: [&str; 2] = ["wiki.css", "wiki.js"];
+const VENDOR_ASSETS: [&str; 3] = ["highlight.min.css", "highlight.min.js", "mermaid.min.js"];
+
+/// Renders a published wiki into a static HTML site that needs no server or
+/// network: highlighting and diagrams come from scripts vendored next to the
+/// site assets.
+#[derive(Clone)]
+pub struct SiteExporter {
+    assets_dir: PathBuf,
+}
+
+#[derive(Debug)]
+pub struct SiteExport {
+    pub pages: usize,
+}
+
+#[derive(Serialize)]
+struct SearchEntry {
+    title: String,
+    url: String,
+    section: Option<String>,
+    text: String,
+}
+
+struct RenderedPage {
+    html: String,
+    text: String,
+}
+
+impl SiteExporter {
+    pub fn new(assets_dir: PathBuf) -> Self {
+        Self { assets_dir }
+    }
+
+    /// Writes the site for the wiki in `wiki_dir` into `target_dir`. The site
+    /// is staged next to the target and renamed into place, replacing any
+    /// earlier export. Fails when a vendored script is missing, since the
+    /// site would lose highlighting or diagrams.
+    pub async fn export(&self, wiki_dir: &Path, target_dir: &Path) -> Result<SiteExport> {
+        let mut missing_assets = Vec::new();
+        for name in VENDOR_ASSETS {
+            if !fs::try_exists(self.assets_dir.join("vendor").join(name)).await? {
+                missing_assets.push(name);
+            }
+        }
+        if !missing_assets.is_empty() {
+            bail!(
+                "missing vendored export assets in {}: {} (run vendor/fetch.sh)",
+                self.assets_dir.join("vendor").display(),
+                missing_assets.join(", ")
+            );
+        }
+
+        let manifest_path = wiki_dir.join(WIKI_MANIFEST_FILE_NAME);
+        let raw = fs::read(&manifest_path)
+            .await
+            .with_context(|| format!("failed to read {}", manifest_path.display()))?;
+        let manifest: WikiManifest = serde_json::from_slice(&raw)
+            .with_context(|| format!("invalid wiki manifest {}", manifest_path.display()))?;
+
+        let staging_dir = staging_path(target_dir);
+        let _ = fs::remove_dir_all(&staging_dir).await;
+        fs::create_dir_all(staging_dir.join("assets").join("vendor")).await?;
+
+        for name in SITE_ASSETS {
+            let source = self.assets_dir.join(name);
+            fs::copy(&source, staging_dir.join("assets").join(name))
+                .await
+                .with_context(|| format!("failed to copy export asset {}", source.display()))?;
+        }
+
+        for name in VENDOR_ASSETS {
+            let source = self.assets_dir.join("vendor").join(name);
+            fs::copy(
+                &source,
+                staging_dir.join("assets").join("vendor").join(name),
+            )
+            .await
+            .with_context(|| format!("failed to copy export asset {}", source.display()))?;
+        }
+
+        let layout = Layout {
+            manifest: &manifest,
+        };
+        let mut search_index = Vec::new();
+
+        let index_markdown = fs::read_to_string(wiki_dir.join(&manifest.index_file))
+            .await
+            .with_context(|| format!("failed to read {}", manifest.index_file))?;
+        let index = render_markdown(&index_markdown);
+        fs::write(
+            staging_dir.join("index.html"),
+            layout.render(&manifest.title, "index.html", &index.html),
+        )
+        .await?;
+
+        for section in &manifest.sections {
+            let markdown = fs::read_to_string(wiki_dir.join(&section.index_file))
+                .await
+                .with_context(|| format!("failed to read {}", section.index_file))?;
+            let url = html_file_name(&section.index_file);
+            let rendered = render_markdown(&markdown);
+
+            write_page(
+                &staging_dir.join(&url),
+                layout.render(&section.title, &url, &rendered.html),
+            )
+            .await?;
+        }
+        search_index.push(SearchEntry {
+            title: manifest.title.clone(),
+            url: "index.html".to_string(),
+            section: None,
+            text: index.text,
+        });
+
+        for page in &manifest.pages {
+            let markdown = fs::read_to_string(wiki_dir.join(&page.file_name))
+                .await
+                .with_context(|| format!("failed to read {}", page.file_name))?;
+            let url = html_file_name(&page.file_name);
+            let rendered = render_markdown(&markdown);
+
+            write_page(
+                &staging_dir.join(&url),
+                layout.render(&page.title, &url, &rendered.html),
+            )
+            .await?;
+
+            search_index.push(SearchEntry {
+                title: page.title.clone(),
+                url,
+                section: layout.section_title(page.section_id.as_deref()),
+                text: rendered.text,
+            });
+        }
+
+        // A script rather than JSON: browsers refuse fetch() on file:// pages.
+        fs::write(
+            staging_dir.join("assets").join("search-index.js"),
+            format!(
+                "window.WIKI_SEARCH_INDEX = {};\n",
+                serde_json::to_string(&search_index)?
+            ),
+        )
+        .await?;
+
+        let _ = fs::remove_dir_all(target_dir).await;
+        fs::rename(&staging_dir, target_dir)
+            .await
+            .with_context(|| format!("failed to publish export {}", target_dir.display()))?;
+
+        Ok(SiteExport {
+            pages: manifest.pages.len(),
+        })
+    }
+}
+
+struct Layout<'a> {
+    manifest: &'a WikiManifest,
+}
+
+impl Layout<'_> {
+    /// Links are relative so the site works from any directory; `root`
+    /// climbs from the page at `current_url` back to the site root.
+    fn render(&self, title: &str, current_url: &str, body: &str) -> String {
+        let root = "../".repeat(current_url.matches('/').count());
+
+        let head = format!(
+            r#"<link rel="stylesheet" href="{root}assets/wiki.css">
+<link rel="stylesheet" href="{root}assets/vendor/highlight.min.css">"#
+        );
+        let scripts = format!(
+            r#"<script src="{root}assets/search-index.js"></script>
+<script src="{root}assets/vendor/highlight.min.js"></script>
+<script src="{root}assets/vendor/mermaid.min.js"></script>
+<script src="{root}assets/wiki.js"></script>"#
+        );
+
+        format!(
+            r#"<!doctype html>
+<html lang="es">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{title} · {wiki_title}</title>
+{head}
+</head>
+<body data-root="{root}">
+<aside class="sidebar">
+<a class="wiki-title" href="{root}index.html">{wiki_title}</a>
+<input id="search" type="search" placeholder="Buscar..." autocomplete="off">
+<ul id="search-results"></ul>
+<nav id="wiki-nav">
+{navigation}
+</nav>
+</aside>
+<main class="content">
+{body}
+</main>
+{scripts}
+</body>
+</html>
+"#,
+            title = escape_html(title),
+            wiki_title = escape_html(&self.manifest.title),
+            navigation = self.navigation(current_url, &root),
+        )
+    }
+
+    /// Sidebar following the section tree, one heading per section indented
+    /// by depth; pages no section lists go in a trailing group. Manifests
+    /// written before the tree was recorded fall back to the flat
+    /// `WikiStructure.sections`.
+    fn navigation(&self, current_url: &str, root: &str) -> String {
+        let mut listed = HashSet::new();
+        let mut groups = Vec::new();
+
+        if self.manifest.sections.is_empty() {
+            for section in &self.manifest.structure.sections {
+                let pages = self.section_pages(&section.pages, &mut listed);
+                groups.push((0, section.title.clone(), None, pages));
+            }
+        } else {
+            for section in &self.manifest.sections {
+                let pages = self.section_pages(&section.pages, &mut listed);
+                groups.push((
+                    section.number.matches('.').count(),
+                    format!("{}. {}", section.number, section.title),
+                    Some(html_file_name(&section.index_file)),
+                    pages,
+                ));
+            }
+        }
+
+        let unlisted = self
+            .manifest
+            .pages
+            .iter()
+            .filter(|page| !listed.contains(page.id.as_str()))
+            .collect::<Vec<_>>();
+        groups.push((0, "Otras paginas".to_string(), None, unlisted));
+
+        groups
+            .into_iter()
+            .filter(|(_, _, index_url, pages)| index_url.is_some() || !pages.is_empty())
+            .map(|(depth, title, index_url, pages)| {
+                let link = |url: &str, label: &str| {
+                    let class = if url == current_url {
+                        r#" class="active""#
+                    } else {
+                        ""
+                    };
+                    format!(
+                        r#"<li{class}><a href="{root}{url}">{}</a></li>"#,
+                        escape_html(label)
+                    )
+                };
+
+                let heading = match &index_url {
+                    Some(url) => format!(
+                        r#"<h3 class="depth-{depth}"><a href="{root}{url}">{}</a></h3>"#,
+                        escape_html(&title)
+                    ),
+                    None => format!(r#"<h3 class="depth-{depth}">{}</h3>"#, escape_html(&title)),
+                };
+                let items = pages
+                    .into_iter()
+                    .map(|page| link(&html_file_name(&page.file_name), &page.title))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+
+                if items.is_empty() {
+                    heading
+                } else {
+                    format!("{heading}\n<ul class=\"depth-{depth}\">\n{items}\n</ul>")
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn section_pages<'m>(
+        &'m self,
+        page_ids: &[String],
+        listed: &mut HashSet<&'m str>,
+    ) -> Vec<&'m WikiManifestPage> {
+        page_ids
+            .iter()
+            .filter_map(|page_id| self.manifest.pages.iter().find(|page| page.id == *page_id))
+            .filter(|page| listed.insert(page.id.as_str()))
+            .collect()
+    }
+
+    fn section_title(&self, section_id: Option<&str>) -> Option<String> {
+        let section_id = section_id?;
+        self.manifest
+            .structure
+            .sections
+            .iter()
+            .find(|section| section.id == section_id)
+            .map(|section| section.title.clone())
+    }
+}
+
+/// Markdown to HTML plus the plain text used by the search index. Mermaid
+/// blocks become `<pre class="mermaid">` for the client-side renderer, and
+/// links between wiki pages are pointed at the `.html` files.
+fn render_markdown(markdown: &str) -> RenderedPage {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_FOOTNOTES;
+
+    let mut text = String::new();
+    let mut mermaid_source: Option<String> = None;
+    let mut events = Vec::new();
+
+    for event in Parser::new_ext(markdown, options) {
+        match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(language)))
+                if language.trim() == "mermaid" =>
+            {
+                mermaid_source = Some(String::new());
+            }
+            Event::End(TagEnd::CodeBlock) if mermaid_source.is_some() => {
+                let source = mermaid_source.take().unwrap_or_default();
+                events.push(Event::Html(CowStr::from(format!(
+                    "<pre class=\"mermaid\">{}</pre>\n",
+                    escape_html(&source)
+                ))));
+            }
+            Event::Text(value) if mermaid_source.is_some() => {
+                if let Some(source) = mermaid_source.as_mut() {
+                    source.push_str(&value);
+                }
+            }
+            Event::Start(Tag::Link {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }) => {
+                events.push(Event::Start(Tag::Link {
+                    link_type,
+                    dest_url: rewrite_link(dest_url),
+                    title,
+                    id,
+                }));
+            }
+            Event::Start(Tag::Image {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }) => {
+                let dest_url = if is_safe_link(&dest_url) {
+                    dest_url
+                } else {
+                    CowStr::from("#")
+                };
+                events.push(Event::Start(Tag::Image {
+                    link_type,
+                    dest_url,
+                    title,
+                    id,
+                }));
+            }
+            Event::Text(value) => {
+                text.push_str(&value);
+                text.push(' ');
+                events.push(Event::Text(value));
+            }
+            Event::Code(value) => {
+                text.push_str(&value);
+                text.push(' ');
+                events.push(Event::Code(value));
+            }
+            // Raw HTML comes from model-written markdown, which repository
+            // content can steer, so it is shown as text rather than run.
+            Event::Html(value) | Event::InlineHtml(value) => {
+                events.push(Event::Text(value));
+            }
+            other => events.push(other),
+        }
+    }
+
+    let mut body = String::new();
+    html::push_html(&mut body, events.into_iter());
+
+    RenderedPage {
+        html: body,
+        text: text.split_whitespace().collect::<Vec<_>>().join(" "),
+    }
+}
+
+/// Relative links to `.md` files target the exported `.html` pages instead.
+/// Targets `is_safe_link` rejects point nowhere.
+fn rewrite_link(dest_url: CowStr<'_>) -> CowStr<'_> {
+    if !is_safe_link(&dest_url) {
+        return CowStr::from("#");
+    }
+
+    if dest_url.contains("://") || dest_url.starts_with('#') || dest_url.starts_with("mailto:") {
+        return dest_url;
+    }
+
+    let (path, fragment) = match dest_url.split_once('#') {
+        Some((path, fragment)) => (path, Some(fragment)),
+        None => (dest_url.as_ref(), None),
+    };
+
+    let path = path.trim_start_matches("./");
+    if !path.ends_with(".md") {
+        return dest_url;
+    }
+
+    let rewritten = match fragment {
+        Some(fragment) => format!("{}#{fragment}", html_file_name(path)),
+        None => html_file_name(path),
+    };
+    CowStr::from(rewritten)
+}
+
+/// Relative paths, fragments, `http(s)` and `mailto` links. Browsers ignore
+/// whitespace and control characters inside a scheme, so they are dropped
+/// before looking for one. Protocol-relative `//host` links, which browsers
+/// also accept written with backslashes, are rejected.
+fn is_safe_link(dest_url: &str) -> bool {
+    let cleaned = dest_url
+        .chars()
+        .filter(|character| !character.is_ascii_whitespace() && !character.is_control())
+        .collect::<String>()
+        .to_ascii_lowercase()
+        .replace('\\', "/");
+
+    if cleaned.starts_with("//") {
+        return false;
+    }
+
+    let scheme = cleaned
+        .split_once(':')
+        .map(|(scheme, _)| scheme)
+        .filter(|scheme| !scheme.contains(['/', '?', '#']));
+    match scheme {
+        Some(scheme) => matches!(scheme, "http" | "https" | "mailto"),
+        None => true,
+    }
+}
+
+/// `README.md` files, the wiki's and each section's, become `index.html`.
+fn html_file_name(markdown_file: &str) -> String {
+    if markdown_file == "README.md" {
+        return "index.html".to_string();
+    }
+
+    if let Some(dir) = markdown_file.strip_suffix("/README.md") {
+        return format!("{dir}/index.html");
+    }
+
+    match markdown_file.strip_suffix(".md") {
+        Some(stem) => format!("{stem}.html"),
+        None => format!("{markdown_file}.html"),
+    }
+}
+
+async fn write_page(path: &Path, html: String) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    fs::write(path, html).await?;
+
+    Ok(())
+}
+
+fn staging_path(target_dir: &Path) -> PathBuf {
+    let name = target_dir
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    target_dir.with_file_name(format!("{name}.tmp"))
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn safe_links_are_kept() {
+        for link in [
+            "02-arquitectura/README.md",
+            "../index.html",
+            "#instalacion",
+            "https://example.com/docs",
+            "HTTP://example.com",
+            "mailto:team@example.com",
+        ] {
+            assert!(is_safe_link(link), "{link}");
+        }
+    }
+
+    #[test]
+    fn script_and_protocol_relative_links_are_rejected() {
+        for link in [
+            "javascript:alert(1)",
+            "JavaScript:alert(1)",
+            "java\tscript:alert(1)",
+            " javascript:alert(1)",
+            "data:text/html;base64,PHNjcmlwdD4=",
+            "vbscript:msgbox",
+            "//evil.example.com",
+            "\\\\evil.example.com",
+            "/\\evil.example.com",
+        ] {
+            assert!(!is_safe_link(link), "{link}");
+        }
+    }
+
+    #[test]
+    fn rewrite_link_neutralizes_unsafe_targets() {
+        assert_eq!(
+            rewrite_link(CowStr::from("javascript:alert(1)")).as_ref(),
+            "#"
+        );
+        assert_eq!(
+            rewrite_link(CowStr::from("data:text/html,x.md")).as_ref(),
+            "#"
+        );
+    }
+
+    #[test]
+    fn rewrite_link_targets_exported_pages() {
+        assert_eq!(
+            rewrite_link(CowStr::from("./01-inicio.md#uso")).as_ref(),
+            "01-inicio.html#uso"
+        );
+        assert_eq!(
+            rewrite_link(CowStr::from("02-arquitectura/README.md")).as_ref(),
+            "02-arquitectura/index.html"
+        );
+        assert_eq!(
+            rewrite_link(CowStr::from("https://example.com/a.md")).as_ref(),
+            "https://example.com/a.md"
+        );
+    }
+}
@@ -0,0 +1,215 @@
+use crate::models::WikiVersion;
+use anyhow::{Context, Result};
+use chrono::Utc;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::fs;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+const VERSIONS_DIR: &str = "versions";
+const EXPORTS_DIR: &str = "exports";
+const CURRENT_LINK: &str = "current";
+
+/// Each generation run writes into its own directory under
+/// `{repo_id}/versions/`; readers follow the `{repo_id}/current` symlink,
+/// which only moves once a run has finished.
+#[derive(Clone)]
+pub struct WikiVersions {
+    root: PathBuf,
+    retain: usize,
+    switch_lock: Arc<Mutex<()>>,
+}
+
+impl WikiVersions {
+    pub fn new(root: PathBuf, retain: usize) -> Self {
+        Self {
+            root,
+            retain,
+            switch_lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// Stable path of the published wiki.
+    pub fn current_dir(&self, repo_id: Uuid) -> PathBuf {
+        self.repo_dir(repo_id).join(CURRENT_LINK)
+    }
+
+    pub fn version_dir(&self, repo_id: Uuid, version: &str) -> PathBuf {
+        self.versions_dir(repo_id).join(version)
+    }
+
+    /// Where the static HTML site of a version is exported to.
+    pub fn export_dir(&self, repo_id: Uuid, version: &str) -> PathBuf {
+        self.repo_dir(repo_id).join(EXPORTS_DIR).join(version)
+    }
+
+    pub async fn current_version(&self, repo_id: Uuid) -> Result<Option<String>> {
+        match fs::read_link(self.current_dir(repo_id)).await {
+            Ok(target) => Ok(target
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Creates an empty directory for a new run. Ids start with a UTC
+    /// timestamp so they sort oldest first, followed by the commit or run id.
+    pub async fn create(
+        &self,
+        repo_id: Uuid,
+        commit_sha: Option<&str>,
+        run_id: Uuid,
+    ) -> Result<(String, PathBuf)> {
+        let suffix = commit_sha
+            .map(|sha| {
+                sha.chars()
+                    .filter(char::is_ascii_alphanumeric)
+                    .take(12)
+                    .collect::<String>()
+            })
+            .filter(|sha| !sha.is_empty())
+            .unwrap_or_else(|| run_id.simple().to_string()[..8].to_string());
+
+        let version = format!("{}-{suffix}", Utc::now().format("%Y%m%dT%H%M%S%3fZ"));
+        let dir = self.version_dir(repo_id, &version);
+        fs::create_dir_all(&dir)
+            .await
+            .with_context(|| format!("failed to create wiki version {}", dir.display()))?;
+
+        Ok((version, dir))
+    }
+
+    /// Publishes `version` and prunes versions beyond the retention limit.
+    pub async fn activate(&self, repo_id: Uuid, version: &str) -> Result<()> {
+        let _guard = self.switch_lock.lock().await;
+
+        self.switch(repo_id, version).await?;
+        self.prune(repo_id).await
+    }
+
+    /// Removes a version and its static export.
+    pub async fn discard(&self, repo_id: Uuid, version: &str) -> Result<()> {
+        for dir in [
+            self.version_dir(repo_id, version),
+            self.export_dir(repo_id, version),
+        ] {
+            match fs::remove_dir_all(&dir).await {
+                Ok(()) => {}
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound => {}
+                Err(error) => return Err(error.into()),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Retained versions, newest first.
+    pub async fn list(&self, repo_id: Uuid) -> Result<Vec<WikiVersion>> {
+        let current = self.current_version(repo_id).await?;
+
+        Ok(self
+            .version_ids(repo_id)
+            .await?
+            .into_iter()
+            .rev()
+            .map(|id| WikiVersion {
+                current: current.as_deref() == Some(id.as_str()),
+                id,
+            })
+            .collect())
+    }
+
+    /// Publishes `version`, or the version before the current one when none is
+    /// given. Returns `None` when there is no such version.
+    pub async fn rollback(&self, repo_id: Uuid, version: Option<&str>) -> Result<Option<String>> {
+        let _guard = self.switch_lock.lock().await;
+
+        let ids = self.version_ids(repo_id).await?;
+        let target = match version {
+            Some(version) => ids.iter().find(|id| *id == version).cloned(),
+            None => {
+                let current = self.current_version(repo_id).await?;
+                ids.iter()
+                    .position(|id| current.as_deref() == Some(id.as_str()))
+                    .filter(|position| *position > 0)
+                    .map(|position| ids[position - 1].clone())
+            }
+        };
+
+        let Some(target) = target else {
+            return Ok(None);
+        };
+
+        self.switch(repo_id, &target).await?;
+        Ok(Some(target))
+    }
+
+    /// Replaces the `current` symlink through a rename, which is atomic.
+    async fn switch(&self, repo_id: Uuid, version: &str) -> Result<()> {
+        let repo_dir = self.repo_dir(repo_id);
+        let tmp_link = repo_dir.join(format!("{CURRENT_LINK}.tmp"));
+
+        let _ = fs::remove_file(&tmp_link).await;
+        fs::symlink(Path::new(VERSIONS_DIR).join(version), &tmp_link)
+            .await
+            .with_context(|| format!("failed to link wiki version {version}"))?;
+        fs::rename(&tmp_link, repo_dir.join(CURRENT_LINK))
+            .await
+            .with_context(|| format!("failed to publish wiki version {version}"))?;
+
+        Ok(())
+    }
+
+    /// Keeps the newest `retain` versions plus the current one, and drops
+    /// loose files left by the layout that predates versioning.
+    async fn prune(&self, repo_id: Uuid) -> Result<()> {
+        let current = self.current_version(repo_id).await?;
+        let ids = self.version_ids(repo_id).await?;
+        let expired = ids.len().saturating_sub(self.retain.max(1));
+
+        for id in &ids[..expired] {
+            if current.as_deref() == Some(id.as_str()) {
+                continue;
+            }
+
+            self.discard(repo_id, id).await?;
+        }
+
+        let mut entries = fs::read_dir(self.repo_dir(repo_id)).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if entry.file_type().await?.is_file() {
+                fs::remove_file(entry.path()).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn version_ids(&self, repo_id: Uuid) -> Result<Vec<String>> {
+        let mut entries = match fs::read_dir(self.versions_dir(repo_id)).await {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => return Err(error.into()),
+        };
+
+        let mut ids = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            if entry.file_type().await?.is_dir() {
+                ids.push(entry.file_name().to_string_lossy().to_string());
+            }
+        }
+
+        ids.sort();
+        Ok(ids)
+    }
+
+    fn repo_dir(&self, repo_id: Uuid) -> PathBuf {
+        self.root.join(repo_id.to_string())
+    }
+
+    fn versions_dir(&self, repo_id: Uuid) -> PathBuf {
+        self.repo_dir(repo_id).join(VERSIONS_DIR)
+    }
+}
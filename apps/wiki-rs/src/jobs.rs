@@ -0,0 +1,245 @@
+use crate::models::{
+    DocGenerationResponse, JobPhase, JobStatus, JobTimings, PageGenerationError, PhaseTiming,
+};
+use anyhow::Result;
+use chrono::{Duration, Utc};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex as StdMutex};
+use tokio::sync::{Mutex, OwnedMutexGuard, RwLock, Semaphore};
+use tracing::{error, info};
+use uuid::Uuid;
+
+const FINISHED_JOB_TTL_HOURS: i64 = 24;
+
+#[derive(Clone)]
+pub struct JobRegistry {
+    jobs: Arc<RwLock<HashMap<Uuid, JobStatus>>>,
+    workers: Arc<Semaphore>,
+    /// One lock per repository, so two runs never race to publish its wiki
+    /// or to write its vectors.
+    repo_locks: RepoLocks,
+}
+
+type RepoLocks = Arc<StdMutex<HashMap<Uuid, Arc<Mutex<()>>>>>;
+
+/// Holds a repository lock; dropping it releases the lock and forgets the
+/// repository once nobody else is holding or waiting on it.
+pub struct RepoGuard {
+    repo_id: Uuid,
+    repo_locks: RepoLocks,
+    guard: Option<OwnedMutexGuard<()>>,
+}
+
+impl Drop for RepoGuard {
+    fn drop(&mut self) {
+        self.guard.take();
+
+        let mut locks = self
+            .repo_locks
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if locks
+            .get(&self.repo_id)
+            .is_some_and(|lock| Arc::strong_count(lock) == 1)
+        {
+            locks.remove(&self.repo_id);
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct JobHandle {
+    job_id: Uuid,
+    registry: JobRegistry,
+}
+
+impl JobRegistry {
+    pub fn new(workers: usize) -> Self {
+        Self {
+            jobs: Arc::new(RwLock::new(HashMap::new())),
+            workers: Arc::new(Semaphore::new(workers.max(1))),
+            repo_locks: Arc::new(StdMutex::new(HashMap::new())),
+        }
+    }
+
+    /// Registers a queued job and runs `task` once earlier jobs for the same
+    /// repository have finished and a worker slot is free.
+    pub async fn enqueue<F, Fut>(&self, repo_id: Uuid, task: F) -> Uuid
+    where
+        F: FnOnce(JobHandle) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<DocGenerationResponse>> + Send + 'static,
+    {
+        let job_id = Uuid::new_v4();
+
+        {
+            let mut jobs = self.jobs.write().await;
+            prune_finished(&mut jobs);
+            jobs.insert(job_id, new_status(job_id, repo_id));
+        }
+
+        let handle = JobHandle {
+            job_id,
+            registry: self.clone(),
+        };
+
+        let registry = self.clone();
+        let workers = self.workers.clone();
+        tokio::spawn(async move {
+            // Waiting on the repository first keeps a queued job from holding
+            // a worker slot it cannot use yet.
+            let _repo_guard = registry.lock_repo(repo_id).await;
+            let Ok(_permit) = workers.acquire_owned().await else {
+                handle.fail("worker pool is closed".to_string()).await;
+                return;
+            };
+
+            match tokio::spawn(task(handle.clone())).await {
+                Ok(Ok(result)) => handle.complete(result).await,
+                Ok(Err(error)) => handle.fail(format!("{error:#}")).await,
+                Err(error) => handle.fail(format!("job panicked: {error}")).await,
+            }
+        });
+
+        job_id
+    }
+
+    /// Waits until no job or other holder is working on the repository.
+    pub async fn lock_repo(&self, repo_id: Uuid) -> RepoGuard {
+        let lock = self
+            .repo_locks
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .entry(repo_id)
+            .or_default()
+            .clone();
+
+        RepoGuard {
+            repo_id,
+            repo_locks: self.repo_locks.clone(),
+            guard: Some(lock.lock_owned().await),
+        }
+    }
+
+    pub async fn get(&self, job_id: Uuid) -> Option<JobStatus> {
+        self.jobs.read().await.get(&job_id).cloned()
+    }
+
+    async fn update(&self, job_id: Uuid, apply: impl FnOnce(&mut JobStatus)) {
+        if let Some(status) = self.jobs.write().await.get_mut(&job_id) {
+            apply(status);
+        }
+    }
+}
+
+impl JobHandle {
+    pub fn id(&self) -> Uuid {
+        self.job_id
+    }
+
+    pub async fn set_phase(&self, phase: JobPhase) {
+        self.registry
+            .update(self.job_id, |status| enter_phase(status, phase))
+            .await;
+    }
+
+    pub async fn record_page_error(&self, page_error: PageGenerationError) {
+        self.registry
+            .update(self.job_id, |status| status.page_errors.push(page_error))
+            .await;
+    }
+
+    async fn complete(&self, result: DocGenerationResponse) {
+        info!(job_id = %self.job_id, "job finished");
+        self.registry
+            .update(self.job_id, |status| {
+                enter_phase(status, JobPhase::Done);
+                status.result = Some(result);
+            })
+            .await;
+    }
+
+    async fn fail(&self, message: String) {
+        error!(job_id = %self.job_id, "job failed: {message}");
+        self.registry
+            .update(self.job_id, |status| {
+                enter_phase(status, JobPhase::Failed);
+                status.error = Some(message);
+            })
+            .await;
+    }
+}
+
+fn new_status(job_id: Uuid, repo_id: Uuid) -> JobStatus {
+    JobStatus {
+        job_id,
+        repo_id,
+        phase: JobPhase::Queued,
+        error: None,
+        page_errors: Vec::new(),
+        result: None,
+        timings: JobTimings {
+            queued_at: Utc::now(),
+            started_at: None,
+            finished_at: None,
+            phases: Vec::new(),
+        },
+    }
+}
+
+fn enter_phase(status: &mut JobStatus, phase: JobPhase) {
+    let now = Utc::now();
+    let name = phase_name(&phase);
+    let is_terminal = matches!(phase, JobPhase::Done | JobPhase::Failed);
+
+    status.phase = phase;
+    status.timings.started_at.get_or_insert(now);
+
+    if status
+        .timings
+        .phases
+        .last()
+        .is_some_and(|current| current.phase == name && current.duration_ms.is_none())
+    {
+        return;
+    }
+
+    if let Some(current) = status.timings.phases.last_mut()
+        && current.duration_ms.is_none()
+    {
+        current.duration_ms = Some((now - current.started_at).num_milliseconds());
+    }
+
+    if is_terminal {
+        status.timings.finished_at = Some(now);
+        return;
+    }
+
+    status.timings.phases.push(PhaseTiming {
+        phase: name.to_string(),
+        started_at: now,
+        duration_ms: None,
+    });
+}
+
+fn phase_name(phase: &JobPhase) -> &'static str {
+    match phase {
+        JobPhase::Queued => "queued",
+        JobPhase::Planning => "planning",
+        JobPhase::Indexing => "indexing",
+        JobPhase::Writing { .. } => "writing",
+        JobPhase::Done => "done",
+        JobPhase::Failed => "failed",
+    }
+}
+
+fn prune_finished(jobs: &mut HashMap<Uuid, JobStatus>) {
+    let cutoff = Utc::now() - Duration::hours(FINISHED_JOB_TTL_HOURS);
+
+    jobs.retain(|_, status| {
+        status
+            .timings
+            .finished_at
+            .is_none_or(|finished_at| finished_at > cutoff)
+    });
+}
@@ -1,49 +1,123 @@
+use crate::models::{ChatMessage, ChatRole, TokenUsage};
+use crate::resilience::{CallError, RetryPolicy, Upstream};
 use anyhow::{Context, Result, anyhow};
 use async_openai::Client;
 use async_openai::config::OpenAIConfig;
+use async_openai::error::OpenAIError;
 use async_openai::types::chat::{
-    ChatCompletionRequestMessage, ChatCompletionRequestUserMessageArgs,
-    CreateChatCompletionRequest, CreateChatCompletionResponse, ResponseFormat,
-    ResponseFormatJsonSchema,
+    ChatCompletionRequestAssistantMessageArgs, ChatCompletionRequestMessage,
+    ChatCompletionRequestSystemMessageArgs, ChatCompletionRequestUserMessageArgs,
+    ChatCompletionStreamOptions, CreateChatCompletionRequest, CreateChatCompletionResponse,
+    CreateChatCompletionStreamResponse, ResponseFormat, ResponseFormatJsonSchema,
 };
 use async_openai::types::embeddings::{CreateEmbeddingRequest, EmbeddingInput};
+use backoff::ExponentialBackoff;
+use futures::stream::{self, BoxStream, StreamExt};
 use schemars::JsonSchema;
 use serde::de::DeserializeOwned;
+use std::time::Duration;
 
-const DEFAULT_CHAT_MODEL: &str = "qwen3.5:9b";
-const DEFAULT_EMBED_MODEL: &str = "nomic-embed-text-v2-moe:latest";
+const DEFAULT_API_KEY: &str = "dummy";
+const DIMENSION_PROBE_TEXT: &str = "dimension probe";
+
+pub enum ChatStreamEvent {
+    Token(String),
+    Usage(TokenUsage),
+}
+
+#[derive(Clone, Debug)]
+pub struct LlmOptions {
+    pub chat_model: String,
+    pub embed_model: String,
+    pub api_key: Option<String>,
+    pub chat_retry: RetryPolicy,
+    pub embed_retry: RetryPolicy,
+}
 
 #[derive(Clone)]
 pub struct LlmClient {
     client: Client<OpenAIConfig>,
     chat_model: String,
     embed_model: String,
+    upstream: Upstream,
+    chat_retry: RetryPolicy,
+    embed_retry: RetryPolicy,
 }
 
 impl LlmClient {
-    pub fn new(base_url: String) -> Self {
+    pub fn new(base_url: String, options: LlmOptions, upstream: Upstream) -> Self {
         let normalized = base_url.trim_end_matches('/').to_string();
+        let api_key = options
+            .api_key
+            .unwrap_or_else(|| DEFAULT_API_KEY.to_string());
         let config = OpenAIConfig::new()
             .with_api_base(normalized)
-            .with_api_key("dummy");
+            .with_api_key(api_key);
+
+        // Retries are handled by the shared resilience layer, so the client's
+        // own rate-limit backoff gives up immediately.
+        let backoff = ExponentialBackoff {
+            max_elapsed_time: Some(Duration::ZERO),
+            ..Default::default()
+        };
+
+        Self {
+            client: Client::with_config(config).with_backoff(backoff),
+            chat_model: options.chat_model,
+            embed_model: options.embed_model,
+            upstream,
+            chat_retry: options.chat_retry,
+            embed_retry: options.embed_retry,
+        }
+    }
+
+    pub fn chat_model(&self) -> &str {
+        &self.chat_model
+    }
+
+    pub fn embed_model(&self) -> &str {
+        &self.embed_model
+    }
 
+    /// Same endpoint and chat model, different embedding model.
+    pub fn with_embed_model(&self, embed_model: &str) -> Self {
         Self {
-            client: Client::with_config(config),
-            chat_model: DEFAULT_CHAT_MODEL.to_string(),
-            embed_model: DEFAULT_EMBED_MODEL.to_string(),
+            client: self.client.clone(),
+            chat_model: self.chat_model.clone(),
+            embed_model: embed_model.to_string(),
+            upstream: self.upstream.clone(),
+            chat_retry: self.chat_retry,
+            embed_retry: self.embed_retry,
         }
     }
 
     pub async fn check_connection(&self) -> Result<()> {
-        self.client
-            .models()
-            .list()
+        self.upstream
+            .call("list models", &self.chat_retry, || async {
+                self.client.models().list().await.map_err(classify_error)
+            })
             .await
             .with_context(|| "failed to connect to OpenAI-compatible endpoint")?;
 
         Ok(())
     }
 
+    /// Embeds a short probe text to learn the real dimension of the embedding model.
+    pub async fn probe_embedding_dimension(&self) -> Result<usize> {
+        let vectors = self
+            .embed_texts(&[DIMENSION_PROBE_TEXT.to_string()])
+            .await
+            .with_context(|| format!("failed to probe embedding model {}", self.embed_model))?;
+
+        match vectors.first().map(Vec::len) {
+            Some(dimension) if dimension > 0 => Ok(dimension),
+            _ => Err(anyhow!(
+                "embedding model {} returned an empty probe vector",
+                self.embed_model
+            )),
+        }
+    }
+
     pub async fn chat(
         &self,
         prompt: &str,
@@ -60,10 +134,97 @@ impl LlmClient {
             ..Default::default()
         };
 
-        let response = self.client.chat().create(request).await?;
+        let response = self.create_chat(request).await?;
         extract_content(response)
     }
 
+    /// Multi-turn completion over an explicit list of system/user/assistant messages.
+    pub async fn chat_messages(
+        &self,
+        messages: &[ChatMessage],
+        temperature: f32,
+        max_tokens: Option<u32>,
+    ) -> Result<String> {
+        let messages = messages
+            .iter()
+            .map(build_message)
+            .collect::<Result<Vec<_>>>()?;
+
+        let request = CreateChatCompletionRequest {
+            model: self.chat_model.clone(),
+            messages,
+            temperature: Some(temperature),
+            max_completion_tokens: max_tokens,
+            ..Default::default()
+        };
+
+        let response = self.create_chat(request).await?;
+        extract_content(response)
+    }
+
+    /// Streams completion tokens as they arrive, followed by the token usage
+    /// when the endpoint reports it. Dropping the stream cancels the request.
+    pub async fn chat_stream(
+        &self,
+        prompt: &str,
+        temperature: f32,
+        max_tokens: Option<u32>,
+    ) -> Result<BoxStream<'static, Result<ChatStreamEvent>>> {
+        let message = build_user_message(prompt)?;
+
+        let request = CreateChatCompletionRequest {
+            model: self.chat_model.clone(),
+            messages: vec![message],
+            temperature: Some(temperature),
+            max_completion_tokens: max_tokens,
+            stream_options: Some(ChatCompletionStreamOptions {
+                include_usage: Some(true),
+                include_obfuscation: None,
+            }),
+            ..Default::default()
+        };
+
+        let response = self
+            .upstream
+            .call("chat stream", &self.chat_retry, || {
+                let request = request.clone();
+                async move {
+                    self.client
+                        .chat()
+                        .create_stream(request)
+                        .await
+                        .map_err(classify_error)
+                }
+            })
+            .await?;
+
+        // The policy's timeout only covers opening the stream; each chunk
+        // gets as long again, so a stalled model ends the stream with an error.
+        let idle_timeout = self.chat_retry.timeout;
+        let chunks = stream::unfold(Some(response), move |response| async move {
+            let mut response = response?;
+            match tokio::time::timeout(idle_timeout, response.next()).await {
+                Ok(Some(item)) => Some((item.map_err(anyhow::Error::from), Some(response))),
+                Ok(None) => None,
+                Err(_) => Some((
+                    Err(anyhow!("chat stream received nothing for {idle_timeout:?}")),
+                    None,
+                )),
+            }
+        });
+
+        Ok(chunks
+            .flat_map(|item| {
+                let events = match item {
+                    Ok(chunk) => stream_events(chunk).into_iter().map(Ok).collect(),
+                    Err(error) => vec![Err(error)],
+                };
+
+                stream::iter(events)
+            })
+            .boxed())
+    }
+
     pub async fn chat_structured<T>(
         &self,
         prompt: &str,
@@ -118,7 +279,20 @@ impl LlmClient {
             ..Default::default()
         };
 
-        let response = self.client.embeddings().create(request).await?;
+        let response = self
+            .upstream
+            .call("embedding", &self.embed_retry, || {
+                let request = request.clone();
+                async move {
+                    self.client
+                        .embeddings()
+                        .create(request)
+                        .await
+                        .map_err(classify_error)
+                }
+            })
+            .await?;
+
         let mut vectors = vec![Vec::<f32>::new(); response.data.len()];
 
         for embedding in response.data {
@@ -133,6 +307,24 @@ impl LlmClient {
         Ok(vectors)
     }
 
+    async fn create_chat(
+        &self,
+        request: CreateChatCompletionRequest,
+    ) -> Result<CreateChatCompletionResponse> {
+        self.upstream
+            .call("chat completion", &self.chat_retry, || {
+                let request = request.clone();
+                async move {
+                    self.client
+                        .chat()
+                        .create(request)
+                        .await
+                        .map_err(classify_error)
+                }
+            })
+            .await
+    }
+
     async fn chat_with_response_format(
         &self,
         prompt: &str,
@@ -151,7 +343,7 @@ impl LlmClient {
             ..Default::default()
         };
 
-        let response = self.client.chat().create(request).await?;
+        let response = self.create_chat(request).await?;
         extract_content(response)
     }
 }
@@ -164,6 +356,75 @@ fn build_user_message(prompt: &str) -> Result<ChatCompletionRequestMessage> {
         .map_err(|error| anyhow!("failed to build user message: {error}"))
 }
 
+fn build_message(message: &ChatMessage) -> Result<ChatCompletionRequestMessage> {
+    let content = message.content.as_str();
+    let built = match message.role {
+        ChatRole::System => ChatCompletionRequestSystemMessageArgs::default()
+            .content(content)
+            .build()
+            .map(ChatCompletionRequestMessage::System),
+        ChatRole::User => ChatCompletionRequestUserMessageArgs::default()
+            .content(content)
+            .build()
+            .map(ChatCompletionRequestMessage::User),
+        ChatRole::Assistant => ChatCompletionRequestAssistantMessageArgs::default()
+            .content(content)
+            .build()
+            .map(ChatCompletionRequestMessage::Assistant),
+    };
+
+    built.map_err(|error| anyhow!("failed to build chat message: {error}"))
+}
+
+fn stream_events(chunk: CreateChatCompletionStreamResponse) -> Vec<ChatStreamEvent> {
+    let mut events = chunk
+        .choices
+        .into_iter()
+        .filter_map(|choice| choice.delta.content)
+        .filter(|content| !content.is_empty())
+        .map(ChatStreamEvent::Token)
+        .collect::<Vec<_>>();
+
+    if let Some(usage) = chunk.usage {
+        events.push(ChatStreamEvent::Usage(TokenUsage {
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+            total_tokens: usage.total_tokens,
+        }));
+    }
+
+    events
+}
+
+/// The client's own backoff is disabled, so `Upstream` is the only retry
+/// layer: transport failures (by the rules of `resilience::classify_error`,
+/// repeated here because async-openai has its own `reqwest`), unreadable
+/// bodies, server errors and rate limits are transient. The client reports
+/// 5xx bodies as an `ApiError` without type or code, which is the only way to
+/// tell them apart from requests the endpoint rejected.
+fn classify_error(error: OpenAIError) -> CallError {
+    let retryable = match &error {
+        OpenAIError::Reqwest(error) => {
+            error.is_timeout() || error.is_connect() || error.is_request() || error.is_body()
+        }
+        OpenAIError::JSONDeserialize(..) => true,
+        OpenAIError::ApiError(api_error) => {
+            (api_error.r#type.is_none() && api_error.code.is_none())
+                || api_error
+                    .code
+                    .as_deref()
+                    .is_some_and(|code| code.contains("rate_limit"))
+        }
+        _ => false,
+    };
+
+    if retryable {
+        CallError::Retryable(error.into())
+    } else {
+        CallError::Permanent(error.into())
+    }
+}
+
 fn extract_content(response: CreateChatCompletionResponse) -> Result<String> {
     response
         .choices
@@ -179,12 +440,12 @@ fn parse_json<T: DeserializeOwned>(raw: &str) -> Result<T> {
         return Ok(value);
     }
 
-    if let (Some(start), Some(end)) = (raw.find('{'), raw.rfind('}')) {
-        if end > start {
-            let slice = &raw[start..=end];
-            if let Ok(value) = serde_json::from_str::<T>(slice) {
-                return Ok(value);
-            }
+    if let (Some(start), Some(end)) = (raw.find('{'), raw.rfind('}'))
+        && end > start
+    {
+        let slice = &raw[start..=end];
+        if let Ok(value) = serde_json::from_str::<T>(slice) {
+            return Ok(value);
         }
     }
 
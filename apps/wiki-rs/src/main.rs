@@ -1,12 +1,17 @@
 mod agents;
 mod api;
+mod budget;
 mod config;
 mod docs;
 mod error;
+mod jobs;
 mod llm;
 mod models;
 mod prompts;
 mod rag;
+mod resilience;
+mod sessions;
+mod storage;
 
 use anyhow::Result;
 use api::build_router;
@@ -20,13 +25,14 @@ use tracing::info;
 async fn main() -> Result<()> {
     init_tracing();
 
-    let config = Arc::new(AppConfig::from_env()?);
+    let config = Arc::new(AppConfig::load()?);
+    let (host, port) = (config.server.host.clone(), config.server.port);
     let state = api::AppState::new(config).await?;
 
     let app = build_router(state).layer(TraceLayer::new_for_http());
 
-    let listener = TcpListener::bind(("0.0.0.0", 3000)).await?;
-    info!("wiki-rs listening on 0.0.0.0:3000");
+    let listener = TcpListener::bind((host.as_str(), port)).await?;
+    info!("wiki-rs listening on {}", listener.local_addr()?);
 
     axum::serve(listener, app).await?;
     Ok(())
@@ -1,3 +1,4 @@
+use chrono::{DateTime, Utc};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -11,24 +12,209 @@ pub struct DocGenerationInput {
     pub repo_path: String,
     #[serde(rename = "repoTree")]
     pub repo_tree: String,
+    /// Regenerates every page and re-runs the planner, ignoring the previous manifest.
+    #[serde(default)]
+    pub force: bool,
+    /// Commit the tree was taken from; names the wiki version when present.
+    #[serde(rename = "commitSha", default)]
+    pub commit_sha: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AskInput {
+    #[serde(rename = "repoId")]
+    pub repo_id: Uuid,
+    pub question: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AskResponse {
+    pub repo_id: Uuid,
+    pub question: String,
+    pub answer: String,
+    pub citations: Vec<Citation>,
 }
 
 #[derive(Debug, Serialize)]
+pub struct AskStreamDone {
+    pub citations: Vec<Citation>,
+    pub usage: Option<TokenUsage>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct TokenUsage {
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    pub total_tokens: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateSessionInput {
+    #[serde(rename = "repoId")]
+    pub repo_id: Uuid,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SessionMessageInput {
+    pub content: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ChatSession {
+    pub id: Uuid,
+    pub repo_id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub messages: Vec<SessionMessage>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SessionMessage {
+    pub role: ChatRole,
+    pub content: String,
+    pub created_at: DateTime<Utc>,
+    pub citations: Vec<Citation>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatRole {
+    System,
+    User,
+    Assistant,
+}
+
+#[derive(Clone, Debug)]
+pub struct ChatMessage {
+    pub role: ChatRole,
+    pub content: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Citation {
+    pub index: usize,
+    pub source: String,
+    pub start_line: Option<usize>,
+    pub end_line: Option<usize>,
+    pub symbol: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
 pub struct DocGenerationResponse {
     pub repo_id: Uuid,
     pub message: String,
     pub generated_pages: usize,
+    pub reused_pages: usize,
+    pub carried_over_pages: usize,
+    pub planner_reused: bool,
+    pub planner_inputs: Vec<String>,
+    pub prompt_trims: Vec<PromptTrim>,
+    pub structure_issues: Vec<StructureIssue>,
+    pub version: String,
     pub output_path: String,
     pub errors: Vec<PageGenerationError>,
 }
 
-#[derive(Debug, Serialize)]
+/// A prompt section that was shortened to fit the model's context window.
+#[derive(Clone, Debug, Serialize)]
+pub struct PromptTrim {
+    pub prompt: String,
+    pub page_id: Option<String>,
+    pub section: String,
+    pub original_tokens: usize,
+    pub kept_tokens: usize,
+    pub detail: String,
+}
+
+/// A problem found in a planned `WikiStructure` and how it was dealt with.
+#[derive(Clone, Debug, Serialize)]
+pub struct StructureIssue {
+    pub prompt: String,
+    pub kind: StructureIssueKind,
+    /// The page or section the issue is about.
+    pub id: String,
+    pub detail: String,
+    pub resolution: IssueResolution,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StructureIssueKind {
+    DuplicatePageId,
+    DuplicateSectionId,
+    UnknownFile,
+    PageWithoutFiles,
+    UnknownPage,
+    RepeatedPage,
+    InvalidSubsection,
+    ParentMismatch,
+    DanglingParent,
+    OrphanPage,
+    UnknownRelatedPage,
+    EmptySection,
+    NoPages,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueResolution {
+    Repaired,
+    FixedByLlm,
+    Unresolved,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PageGenerationError {
     pub page_id: String,
     pub page_title: String,
     pub error: String,
 }
 
+#[derive(Debug, Serialize)]
+pub struct DocGenerationAccepted {
+    pub job_id: Uuid,
+    pub repo_id: Uuid,
+    pub status_url: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "phase", rename_all = "snake_case")]
+pub enum JobPhase {
+    Queued,
+    Planning,
+    Indexing,
+    Writing { page: usize, total: usize },
+    Done,
+    Failed,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct JobStatus {
+    pub job_id: Uuid,
+    pub repo_id: Uuid,
+    #[serde(flatten)]
+    pub phase: JobPhase,
+    pub error: Option<String>,
+    pub page_errors: Vec<PageGenerationError>,
+    pub result: Option<DocGenerationResponse>,
+    pub timings: JobTimings,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct JobTimings {
+    pub queued_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub phases: Vec<PhaseTiming>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PhaseTiming {
+    pub phase: String,
+    pub started_at: DateTime<Utc>,
+    pub duration_ms: Option<i64>,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 pub struct WikiStructure {
     pub title: String,
@@ -66,6 +252,7 @@ pub enum Importance {
 
 #[derive(Clone, Debug)]
 pub struct RetrievedChunk {
+    pub id: String,
     pub content: String,
     pub source: String,
     pub kind: String,
@@ -73,7 +260,16 @@ pub struct RetrievedChunk {
     pub chunk_index: usize,
     pub start_line: Option<usize>,
     pub end_line: Option<usize>,
+    pub symbol: Option<ChunkSymbol>,
     pub score: f32,
+    pub rerank_score: Option<f32>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ChunkSymbol {
+    pub name: String,
+    pub kind: String,
+    pub signature: String,
 }
 
 #[derive(Clone, Debug)]
@@ -83,3 +279,127 @@ pub struct PreparedDoc {
     pub metadata: HashMap<String, serde_json::Value>,
     pub embedding: Vec<f32>,
 }
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProjectIndex {
+    pub collection: String,
+    pub embed_model: String,
+    pub dimension: usize,
+    pub indexed_at: DateTime<Utc>,
+}
+
+/// What a wiki was generated from, kept next to the pages so the next run can
+/// skip the planner and every page whose retrieved context is unchanged.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GenerationManifest {
+    pub tree_hash: String,
+    pub structure: WikiStructure,
+    #[serde(default)]
+    pub planner_inputs: Vec<String>,
+    pub pages: HashMap<String, PageManifest>,
+    pub generated_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PageManifest {
+    pub file_name: String,
+    pub context_hash: String,
+    pub chunks: Vec<ManifestChunk>,
+    #[serde(default)]
+    pub citations: Vec<Citation>,
+    #[serde(default)]
+    pub generated_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ManifestChunk {
+    pub id: String,
+    pub hash: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct WikiVersion {
+    pub id: String,
+    pub current: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct WikiRollbackInput {
+    #[serde(default)]
+    pub version: Option<String>,
+}
+
+/// Machine-readable description of a published wiki, written as `wiki.json`
+/// so clients can build navigation without parsing markdown.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WikiManifest {
+    pub repo_id: Uuid,
+    pub version: String,
+    pub commit_sha: Option<String>,
+    pub title: String,
+    pub description: String,
+    pub index_file: String,
+    pub models: WikiModels,
+    pub started_at: DateTime<Utc>,
+    pub generated_at: DateTime<Utc>,
+    /// Files the planner read in place of, or as, the project README.
+    #[serde(default)]
+    pub planner_inputs: Vec<String>,
+    pub structure: WikiStructure,
+    /// The section tree, parents before their children.
+    #[serde(default)]
+    pub sections: Vec<WikiManifestSection>,
+    pub pages: Vec<WikiManifestPage>,
+    /// Pages that failed in this run; the ones with an earlier version keep
+    /// it, with its original `generated_at`.
+    #[serde(default)]
+    pub errors: Vec<PageGenerationError>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WikiModels {
+    pub chat: String,
+    pub embedding: String,
+    pub rerank: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WikiManifestSection {
+    pub id: String,
+    /// Position in the tree, e.g. `2.1`.
+    pub number: String,
+    pub title: String,
+    pub index_file: String,
+    pub parent: Option<String>,
+    pub pages: Vec<String>,
+    pub subsections: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WikiManifestPage {
+    pub id: String,
+    pub title: String,
+    pub description: String,
+    pub importance: Importance,
+    pub section_id: Option<String>,
+    pub file_name: String,
+    pub related_pages: Vec<RelatedPageLink>,
+    pub citations: Vec<Citation>,
+    pub generated_at: DateTime<Utc>,
+}
+
+/// `file_name` is missing when the related page failed or does not exist.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RelatedPageLink {
+    pub id: String,
+    pub title: Option<String>,
+    pub file_name: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SiteExportResponse {
+    pub repo_id: Uuid,
+    pub version: String,
+    pub output_path: String,
+    pub pages: usize,
+}
@@ -36,13 +36,15 @@ impl PromptStore {
         Ok(Self { prompts })
     }
 
-    pub fn render(&self, key: &str, vars: &[(&str, String)]) -> Result<String> {
-        let template = self
-            .prompts
+    pub fn template(&self, key: &str) -> Result<&str> {
+        self.prompts
             .get(key)
-            .with_context(|| format!("Prompt not found: {key}"))?;
+            .map(String::as_str)
+            .with_context(|| format!("Prompt not found: {key}"))
+    }
 
-        let mut rendered = template.clone();
+    pub fn render(&self, key: &str, vars: &[(&str, String)]) -> Result<String> {
+        let mut rendered = self.template(key)?.to_string();
         for (name, value) in vars {
             let placeholder = format!("{{{{{name}}}}}");
             rendered = rendered.replace(&placeholder, value);
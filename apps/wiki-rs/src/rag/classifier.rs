@@ -1,3 +1,5 @@
+use crate::rag::splitter::ChunkSizes;
+use sha2::{Digest, Sha256};
 use std::path::Path;
 use uuid::Uuid;
 
@@ -7,16 +9,29 @@ pub struct ClassifiedFile {
     pub relative_path: String,
     pub kind: String,
     pub language: String,
+    /// Covers the chunk size the file is split with, so changing the size
+    /// re-splits it like an edit would.
+    pub content_hash: String,
 }
 
-pub fn classify_file(project_id: Uuid, root: &Path, file_path: &Path) -> Option<ClassifiedFile> {
-    let relative = file_path.strip_prefix(root).ok()?;
-    let relative_path = relative.to_string_lossy().replace('\\', "/");
+/// What the path alone says about a file, so files that are never indexed
+/// are never read.
+pub struct FileClass {
+    pub relative_path: String,
+    pub kind: &'static str,
+    pub language: &'static str,
+}
 
-    if relative_path.contains("/.git/") || relative_path.ends_with("/.git") {
+pub fn classify_path(root: &Path, file_path: &Path) -> Option<FileClass> {
+    let relative = file_path.strip_prefix(root).ok()?;
+    if relative
+        .components()
+        .any(|component| component.as_os_str() == ".git")
+    {
         return None;
     }
 
+    let relative_path = relative.to_string_lossy().replace('\\', "/");
     let extension = file_path
         .extension()
         .and_then(|value| value.to_str())
@@ -25,14 +40,35 @@ pub fn classify_file(project_id: Uuid, root: &Path, file_path: &Path) -> Option<
 
     let (kind, language) = classify_extension(&extension);
 
-    Some(ClassifiedFile {
-        project_id,
+    Some(FileClass {
         relative_path,
-        kind: kind.to_string(),
-        language: language.to_string(),
+        kind,
+        language,
     })
 }
 
+pub fn classify_file(
+    project_id: Uuid,
+    class: FileClass,
+    content: &str,
+    sizes: ChunkSizes,
+) -> ClassifiedFile {
+    ClassifiedFile {
+        project_id,
+        content_hash: hash_content(content, sizes.for_kind(class.kind)),
+        relative_path: class.relative_path,
+        kind: class.kind.to_string(),
+        language: class.language.to_string(),
+    }
+}
+
+fn hash_content(content: &str, chunk_size: usize) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(format!("{chunk_size}\n").as_bytes());
+    hasher.update(content.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
 fn classify_extension(extension: &str) -> (&'static str, &'static str) {
     match extension {
         "ts" => ("code", "typescript"),
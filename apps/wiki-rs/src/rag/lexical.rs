@@ -0,0 +1,196 @@
+use crate::storage::write_atomic;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::fs;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+const BM25_K1: f32 = 1.2;
+const BM25_B: f32 = 0.75;
+
+/// BM25 index over the chunk texts of one project, keyed by Qdrant point ID.
+#[derive(Default, Serialize, Deserialize)]
+pub struct LexicalIndex {
+    doc_ids: Vec<String>,
+    doc_lengths: Vec<u32>,
+    postings: HashMap<String, Vec<(u32, u32)>>,
+}
+
+#[derive(Clone)]
+pub struct LexicalStore {
+    dir: PathBuf,
+    cache: Arc<RwLock<HashMap<Uuid, Arc<LexicalIndex>>>>,
+}
+
+impl LexicalIndex {
+    pub fn build(docs: impl IntoIterator<Item = (String, String)>) -> Self {
+        let mut index = Self::default();
+
+        for (id, text) in docs {
+            let doc = index.doc_ids.len() as u32;
+            let tokens = tokenize(&text);
+
+            let mut frequencies: HashMap<String, u32> = HashMap::new();
+            for token in &tokens {
+                *frequencies.entry(token.clone()).or_insert(0) += 1;
+            }
+
+            for (term, frequency) in frequencies {
+                index
+                    .postings
+                    .entry(term)
+                    .or_default()
+                    .push((doc, frequency));
+            }
+
+            index.doc_ids.push(id);
+            index.doc_lengths.push(tokens.len() as u32);
+        }
+
+        index
+    }
+
+    pub fn search(&self, query: &str, limit: usize) -> Vec<(String, f32)> {
+        let total_docs = self.doc_ids.len() as f32;
+        if total_docs == 0.0 {
+            return Vec::new();
+        }
+
+        let average_length = self
+            .doc_lengths
+            .iter()
+            .map(|length| *length as f32)
+            .sum::<f32>()
+            / total_docs;
+
+        let mut terms = tokenize(query);
+        terms.sort();
+        terms.dedup();
+
+        let mut scores: HashMap<u32, f32> = HashMap::new();
+        for term in terms {
+            let Some(postings) = self.postings.get(&term) else {
+                continue;
+            };
+
+            let document_frequency = postings.len() as f32;
+            let idf =
+                ((total_docs - document_frequency + 0.5) / (document_frequency + 0.5) + 1.0).ln();
+
+            for (doc, frequency) in postings {
+                let length = self.doc_lengths[*doc as usize] as f32;
+                let frequency = *frequency as f32;
+                let normalized = frequency * (BM25_K1 + 1.0)
+                    / (frequency
+                        + BM25_K1 * (1.0 - BM25_B + BM25_B * length / average_length.max(1.0)));
+
+                *scores.entry(*doc).or_insert(0.0) += idf * normalized;
+            }
+        }
+
+        let mut ranked = scores.into_iter().collect::<Vec<_>>();
+        ranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+
+        ranked
+            .into_iter()
+            .take(limit)
+            .map(|(doc, score)| (self.doc_ids[doc as usize].clone(), score))
+            .collect()
+    }
+}
+
+impl LexicalStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            cache: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    pub async fn save(&self, project_id: Uuid, index: LexicalIndex) -> Result<()> {
+        let serialized = serde_json::to_vec(&index)?;
+        write_atomic(&self.index_path(project_id), serialized)
+            .await
+            .context("failed to write lexical index")?;
+
+        self.cache.write().await.insert(project_id, Arc::new(index));
+        Ok(())
+    }
+
+    pub async fn load(&self, project_id: Uuid) -> Result<Option<Arc<LexicalIndex>>> {
+        if let Some(index) = self.cache.read().await.get(&project_id) {
+            return Ok(Some(index.clone()));
+        }
+
+        let path = self.index_path(project_id);
+        let raw = match fs::read(&path).await {
+            Ok(raw) => raw,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error.into()),
+        };
+
+        let index: Arc<LexicalIndex> = Arc::new(
+            serde_json::from_slice(&raw)
+                .with_context(|| format!("invalid lexical index {}", path.display()))?,
+        );
+
+        self.cache.write().await.insert(project_id, index.clone());
+        Ok(Some(index))
+    }
+
+    fn index_path(&self, project_id: Uuid) -> PathBuf {
+        self.dir.join(format!("{project_id}.json"))
+    }
+}
+
+/// Lowercased words plus their camelCase/snake_case parts, so both
+/// `search_by_project` and `project` match the same identifier.
+fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+
+    for word in text.split(|c: char| !(c.is_alphanumeric() || c == '_')) {
+        if word.chars().count() < 2 {
+            continue;
+        }
+
+        let parts = split_identifier(word);
+        if parts.len() > 1 {
+            tokens.extend(parts.into_iter().filter(|part| part.chars().count() >= 2));
+        }
+
+        tokens.push(word.to_lowercase());
+    }
+
+    tokens
+}
+
+fn split_identifier(word: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut previous_lower = false;
+
+    for c in word.chars() {
+        if c == '_' || (c.is_uppercase() && previous_lower) {
+            if !current.is_empty() {
+                parts.push(std::mem::take(&mut current));
+            }
+
+            previous_lower = false;
+            if c == '_' {
+                continue;
+            }
+        }
+
+        previous_lower = c.is_lowercase() || c.is_ascii_digit();
+        current.extend(c.to_lowercase());
+    }
+
+    if !current.is_empty() {
+        parts.push(current);
+    }
+
+    parts
+}
@@ -1,44 +1,114 @@
 mod classifier;
+mod lexical;
 pub mod qdrant;
+mod registry;
+mod rerank;
 mod splitter;
+mod symbols;
 
 use crate::llm::LlmClient;
-use crate::models::{PreparedDoc, RetrievedChunk, WikiPage};
-use anyhow::Result;
-use classifier::{ClassifiedFile, classify_file};
-use qdrant::QdrantClient;
+use crate::models::{PreparedDoc, ProjectIndex, RetrievedChunk, WikiPage};
+use anyhow::{Result, anyhow};
+use chrono::Utc;
+use classifier::{ClassifiedFile, classify_file, classify_path};
+use futures::stream::{self, StreamExt};
+use lexical::LexicalIndex;
+use qdrant::{LEGACY_COLLECTION, QdrantClient};
 use splitter::split_document as split_text;
 use std::collections::{HashMap, HashSet};
 use std::path::Path;
+use symbols::{Symbol, extract_symbols};
 use tokio::fs;
+use tracing::{info, warn};
 use uuid::Uuid;
 use walkdir::WalkDir;
 
+pub use lexical::LexicalStore;
+pub use registry::IndexRegistry;
+pub use rerank::Reranker;
+pub use splitter::ChunkSizes;
+
+const RELEVANT_FILE_BOOST: f32 = 0.15;
+const SYMBOL_MATCH_BOOST: f32 = 0.1;
+const VECTOR_CANDIDATES: usize = 40;
+const LEXICAL_CANDIDATES: usize = 40;
+const RRF_K: f32 = 60.0;
+
 #[derive(Clone)]
 pub struct RagEngine {
     qdrant: QdrantClient,
     llm: LlmClient,
+    lexical: LexicalStore,
+    registry: IndexRegistry,
+    reranker: Reranker,
+    indexing: IndexingOptions,
+    retrieval: RetrievalOptions,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct IndexingOptions {
+    pub batch_size: usize,
+    pub concurrency: usize,
+    pub chunk_sizes: ChunkSizes,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RetrievalOptions {
+    pub vector_weight: f32,
+    pub lexical_weight: f32,
+    pub max_chunks_per_file: usize,
+    pub max_chunks: usize,
+    pub rerank_candidates: usize,
 }
 
 impl RagEngine {
-    pub fn new(qdrant: QdrantClient, llm: LlmClient) -> Self {
-        Self { qdrant, llm }
+    pub fn new(
+        qdrant: QdrantClient,
+        llm: LlmClient,
+        lexical: LexicalStore,
+        registry: IndexRegistry,
+        reranker: Reranker,
+        indexing: IndexingOptions,
+        retrieval: RetrievalOptions,
+    ) -> Self {
+        Self {
+            qdrant,
+            llm,
+            lexical,
+            registry,
+            reranker,
+            indexing,
+            retrieval,
+        }
     }
 
+    /// Indexes into the active collection. Retrieval keeps using the project's
+    /// previous collection until indexation finishes and the registry switches.
     pub async fn new_indexation(&self, project_id: Uuid, project_path: &Path) -> Result<()> {
         self.qdrant.ensure_collection().await?;
-        self.qdrant.delete_project_vectors(project_id).await?;
-
+        let previous = self
+            .registry
+            .get(project_id)
+            .await
+            .map(|entry| entry.collection);
+
+        let indexed_hashes = self.qdrant.fetch_file_hashes(project_id).await?;
+        let mut seen_files = HashSet::new();
+        let mut stale_files = Vec::new();
+        let mut unchanged = 0usize;
         let mut chunks = Vec::new();
 
-        for entry in WalkDir::new(project_path) {
+        let entries = WalkDir::new(project_path)
+            .into_iter()
+            .filter_entry(|entry| entry.file_name() != ".git");
+        for entry in entries {
             let entry = entry?;
             if !entry.file_type().is_file() {
                 continue;
             }
 
             let full_path = entry.path();
-            let Some(classified) = classify_file(project_id, project_path, full_path) else {
+            let Some(class) = classify_path(project_path, full_path) else {
                 continue;
             };
 
@@ -47,18 +117,290 @@ impl RagEngine {
                 Err(_) => continue,
             };
 
-            let file_chunks = split_chunks(&classified, &content);
+            let classified = classify_file(project_id, class, &content, self.indexing.chunk_sizes);
+
+            seen_files.insert(classified.relative_path.clone());
+
+            match indexed_hashes.get(&classified.relative_path) {
+                Some(hash) if *hash == classified.content_hash => {
+                    unchanged += 1;
+                    continue;
+                }
+                Some(_) => stale_files.push(classified.relative_path.clone()),
+                None => {}
+            }
+
+            let file_chunks = split_chunks(&classified, &content, self.indexing.chunk_sizes);
             chunks.extend(file_chunks);
         }
 
-        if chunks.is_empty() {
+        let changed = stale_files.len();
+        let removed = indexed_hashes
+            .keys()
+            .filter(|source| !seen_files.contains(*source))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        info!(
+            project_id = %project_id,
+            unchanged,
+            changed,
+            removed = removed.len(),
+            chunks = chunks.len(),
+            "incremental indexation plan computed"
+        );
+
+        stale_files.extend(removed);
+        self.qdrant
+            .delete_file_vectors(project_id, &stale_files)
+            .await?;
+
+        let failed_files = if chunks.is_empty() {
+            Vec::new()
+        } else {
+            self.embed_and_upsert(project_id, chunks).await?
+        };
+
+        // A project moving to another collection only switches once every
+        // file made it, otherwise retrieval would lose the failed ones.
+        if !failed_files.is_empty() {
+            if previous.as_deref() != Some(self.qdrant.collection_name()) {
+                return Err(anyhow!(
+                    "indexation failed for {} files, keeping the project on its previous collection",
+                    failed_files.len()
+                ));
+            }
+
+            warn!(
+                project_id = %project_id,
+                failed = failed_files.len(),
+                "indexation left files to retry on the next run"
+            );
+            self.rebuild_lexical_index(project_id).await?;
             return Ok(());
         }
 
-        let texts: Vec<String> = chunks.iter().map(|chunk| chunk.text.clone()).collect();
+        self.rebuild_lexical_index(project_id).await?;
+        self.switch_project(project_id, previous).await?;
+        Ok(())
+    }
+
+    pub async fn project_index(&self, project_id: Uuid) -> Option<ProjectIndex> {
+        self.registry.get(project_id).await
+    }
+
+    /// Re-embeds the chunks stored for the project with the active embedding
+    /// model, then switches the project over. Other projects are untouched and
+    /// the project itself stays searchable in its old collection meanwhile.
+    /// Projects missing from the registry are read from the legacy collection.
+    pub async fn reembed_project(&self, project_id: Uuid) -> Result<ProjectIndex> {
+        let previous = self.registry.get(project_id).await;
+        if let Some(entry) = &previous
+            && entry.collection == self.qdrant.collection_name()
+        {
+            return Ok(entry.clone());
+        }
+
+        let source_collection = previous
+            .as_ref()
+            .map(|entry| entry.collection.clone())
+            .unwrap_or_else(|| LEGACY_COLLECTION.to_string());
+        let source_dimension = previous.as_ref().map_or(0, |entry| entry.dimension);
+        let source = self
+            .qdrant
+            .for_collection(source_collection.clone(), source_dimension);
+
+        let documents = source.fetch_documents(project_id).await?;
+        if documents.is_empty() {
+            return Err(anyhow!(
+                "project {project_id} has no chunks in collection {source_collection}"
+            ));
+        }
+
+        info!(
+            project_id = %project_id,
+            from = %source_collection,
+            to = %self.qdrant.collection_name(),
+            chunks = documents.len(),
+            "re-embedding project"
+        );
+
+        self.qdrant.ensure_collection().await?;
+        self.qdrant.delete_project_vectors(project_id).await?;
+
+        let chunks = documents
+            .into_iter()
+            .map(|(id, text, metadata)| Chunk { id, text, metadata })
+            .collect::<Vec<_>>();
+
+        let failed_files = self.embed_and_upsert(project_id, chunks).await?;
+        if !failed_files.is_empty() {
+            self.qdrant.delete_project_vectors(project_id).await?;
+            return Err(anyhow!(
+                "re-embedding failed for {} files, keeping collection {source_collection}",
+                failed_files.len()
+            ));
+        }
+
+        self.rebuild_lexical_index(project_id).await?;
+        self.switch_project(project_id, Some(source_collection))
+            .await
+    }
+
+    /// Registers the active collection for the project and drops its points
+    /// from the collection it was using before, if different.
+    async fn switch_project(
+        &self,
+        project_id: Uuid,
+        previous_collection: Option<String>,
+    ) -> Result<ProjectIndex> {
+        let entry = ProjectIndex {
+            collection: self.qdrant.collection_name().to_string(),
+            embed_model: self.llm.embed_model().to_string(),
+            dimension: self.qdrant.vector_size(),
+            indexed_at: Utc::now(),
+        };
+
+        self.registry.set(project_id, entry.clone()).await?;
+
+        if let Some(previous_collection) = previous_collection
+            && previous_collection != entry.collection
+        {
+            info!(
+                project_id = %project_id,
+                from = %previous_collection,
+                to = %entry.collection,
+                "project switched to new collection"
+            );
+
+            let old = self.qdrant.for_collection(previous_collection, 0);
+            if let Err(error) = old.delete_project_vectors(project_id).await {
+                warn!(
+                    project_id = %project_id,
+                    "failed to clean up previous collection: {error:#}"
+                );
+            }
+        }
+
+        Ok(entry)
+    }
+
+    /// Collection and embedding model retrieval should use for the project.
+    /// Projects missing from the registry were indexed before collections
+    /// were per model, so they are read from the legacy collection.
+    async fn project_target(&self, project_id: Uuid) -> (QdrantClient, LlmClient) {
+        match self.registry.get(project_id).await {
+            Some(entry) if entry.collection == self.qdrant.collection_name() => {
+                (self.qdrant.clone(), self.llm.clone())
+            }
+            Some(entry) => (
+                self.qdrant
+                    .for_collection(entry.collection, entry.dimension),
+                self.llm.with_embed_model(&entry.embed_model),
+            ),
+            None => (
+                self.qdrant.for_collection(LEGACY_COLLECTION.to_string(), 0),
+                self.llm.clone(),
+            ),
+        }
+    }
+
+    /// Rebuilds the BM25 index from every point stored for the project, so files
+    /// skipped by incremental indexation are still searchable.
+    async fn rebuild_lexical_index(&self, project_id: Uuid) -> Result<()> {
+        let contents = self.qdrant.fetch_contents(project_id).await?;
+        let documents = contents.len();
+
+        self.lexical
+            .save(project_id, LexicalIndex::build(contents))
+            .await?;
+
+        info!(project_id = %project_id, documents, "lexical index rebuilt");
+        Ok(())
+    }
+
+    /// Embeds chunks in bounded-concurrency batches and upserts each batch as it
+    /// completes. Files touched by a failed batch are removed again so the next
+    /// run re-indexes them instead of trusting their stored hash. Returns those files.
+    async fn embed_and_upsert(&self, project_id: Uuid, chunks: Vec<Chunk>) -> Result<Vec<String>> {
+        let batch_size = self.indexing.batch_size.max(1);
+        let mut batches = Vec::new();
+        let mut remaining = chunks.into_iter().peekable();
+
+        while remaining.peek().is_some() {
+            batches.push(remaining.by_ref().take(batch_size).collect::<Vec<_>>());
+        }
+
+        let total_batches = batches.len();
+        let failed_sources = stream::iter(batches.into_iter().enumerate())
+            .map(|(index, batch)| async move {
+                let sources = batch
+                    .iter()
+                    .filter_map(|chunk| chunk.metadata.get("source"))
+                    .filter_map(serde_json::Value::as_str)
+                    .map(str::to_string)
+                    .collect::<HashSet<_>>();
+
+                match self.embed_batch(batch).await {
+                    Ok(()) => Vec::new(),
+                    Err(error) => {
+                        warn!(
+                            project_id = %project_id,
+                            batch = index + 1,
+                            total_batches,
+                            "embedding batch failed: {error:#}"
+                        );
+                        sources.into_iter().collect()
+                    }
+                }
+            })
+            .buffer_unordered(self.indexing.concurrency.max(1))
+            .collect::<Vec<Vec<String>>>()
+            .await;
+
+        let failed_batches = failed_sources
+            .iter()
+            .filter(|batch| !batch.is_empty())
+            .count();
+        if failed_batches == 0 {
+            return Ok(Vec::new());
+        }
+
+        let mut failed_files = failed_sources.into_iter().flatten().collect::<Vec<_>>();
+        failed_files.sort();
+        failed_files.dedup();
+        self.qdrant
+            .delete_file_vectors(project_id, &failed_files)
+            .await?;
+
+        if failed_batches == total_batches {
+            return Err(anyhow!("all {total_batches} embedding batches failed"));
+        }
+
+        warn!(
+            project_id = %project_id,
+            failed_batches,
+            total_batches,
+            files = failed_files.len(),
+            "indexation completed with failed embedding batches"
+        );
+
+        Ok(failed_files)
+    }
+
+    async fn embed_batch(&self, batch: Vec<Chunk>) -> Result<()> {
+        let texts: Vec<String> = batch.iter().map(|chunk| chunk.text.clone()).collect();
         let embeddings = self.llm.embed_texts(&texts).await?;
 
-        let prepared_docs = chunks
+        if embeddings.len() != batch.len() {
+            return Err(anyhow!(
+                "expected {} embeddings, received {}",
+                batch.len(),
+                embeddings.len()
+            ));
+        }
+
+        let prepared_docs = batch
             .into_iter()
             .zip(embeddings)
             .map(|(chunk, embedding)| PreparedDoc {
@@ -69,8 +411,7 @@ impl RagEngine {
             })
             .collect::<Vec<_>>();
 
-        self.qdrant.upsert_documents(prepared_docs).await?;
-        Ok(())
+        self.qdrant.upsert_documents(prepared_docs).await
     }
 
     pub async fn retrieve_page_context(
@@ -80,22 +421,10 @@ impl RagEngine {
         page: &WikiPage,
     ) -> Result<Vec<RetrievedChunk>> {
         let query = build_query(section_title, page);
-        let query_embedding = self
-            .llm
-            .embed_texts(&[query])
-            .await?
-            .into_iter()
-            .next()
-            .unwrap_or_default();
-
-        if query_embedding.is_empty() {
-            return Ok(Vec::new());
-        }
-
-        let mut chunks = self
-            .qdrant
-            .search_by_project(project_id, &query_embedding, 40)
-            .await?;
+        let rerank_query = format!(
+            "Section: {section_title}\nPage title: {}\nPage description: {}",
+            page.title, page.description
+        );
 
         let relevant_paths: HashSet<String> = page
             .relevant_files
@@ -103,51 +432,135 @@ impl RagEngine {
             .map(|item| item.replace('\\', "/"))
             .collect();
 
-        chunks.sort_by(|a, b| {
-            let a_boost = if relevant_paths.contains(&a.source) {
-                a.score + 0.15
-            } else {
-                a.score
-            };
+        let page_text = format!("{} {}", page.title, page.description).to_lowercase();
 
-            let b_boost = if relevant_paths.contains(&b.source) {
-                b.score + 0.15
-            } else {
-                b.score
-            };
+        self.retrieve(
+            project_id,
+            &query,
+            &rerank_query,
+            &relevant_paths,
+            &page_text,
+        )
+        .await
+    }
+
+    pub async fn retrieve_question_context(
+        &self,
+        project_id: Uuid,
+        question: &str,
+    ) -> Result<Vec<RetrievedChunk>> {
+        self.retrieve(
+            project_id,
+            question,
+            question,
+            &HashSet::new(),
+            &question.to_lowercase(),
+        )
+        .await
+    }
+
+    /// Hybrid vector + BM25 retrieval fused with RRF, optionally reranked, then
+    /// capped per file and in total.
+    async fn retrieve(
+        &self,
+        project_id: Uuid,
+        query: &str,
+        rerank_query: &str,
+        relevant_paths: &HashSet<String>,
+        mention_text: &str,
+    ) -> Result<Vec<RetrievedChunk>> {
+        let (qdrant, llm) = self.project_target(project_id).await;
+
+        let query_embedding = llm
+            .embed_texts(&[query.to_string()])
+            .await?
+            .into_iter()
+            .next()
+            .unwrap_or_default();
+
+        let mut vector_hits = if query_embedding.is_empty() {
+            Vec::new()
+        } else {
+            qdrant
+                .search_by_project(project_id, &query_embedding, VECTOR_CANDIDATES)
+                .await?
+        };
+
+        vector_hits.sort_by(|a, b| {
+            let a_boost = boosted_score(a, relevant_paths, mention_text);
+            let b_boost = boosted_score(b, relevant_paths, mention_text);
 
             b_boost
                 .partial_cmp(&a_boost)
                 .unwrap_or(std::cmp::Ordering::Equal)
         });
 
+        let lexical_hits = match self.lexical.load(project_id).await? {
+            Some(index) => index.search(query, LEXICAL_CANDIDATES),
+            None => Vec::new(),
+        };
+
+        let mut fused_scores: HashMap<String, f32> = HashMap::new();
+        for (rank, chunk) in vector_hits.iter().enumerate() {
+            *fused_scores.entry(chunk.id.clone()).or_insert(0.0) +=
+                self.retrieval.vector_weight / (RRF_K + rank as f32 + 1.0);
+        }
+
+        for (rank, (id, _)) in lexical_hits.iter().enumerate() {
+            *fused_scores.entry(id.clone()).or_insert(0.0) +=
+                self.retrieval.lexical_weight / (RRF_K + rank as f32 + 1.0);
+        }
+
+        let vector_ids: HashSet<String> =
+            vector_hits.iter().map(|chunk| chunk.id.clone()).collect();
+        let lexical_only = lexical_hits
+            .into_iter()
+            .map(|(id, _)| id)
+            .filter(|id| !vector_ids.contains(id))
+            .collect::<Vec<_>>();
+
+        let mut chunks = vector_hits;
+        chunks.extend(qdrant.fetch_points(&lexical_only).await?);
+
+        chunks.sort_by(|a, b| {
+            let a_fused = fused_scores.get(&a.id).copied().unwrap_or_default();
+            let b_fused = fused_scores.get(&b.id).copied().unwrap_or_default();
+
+            b_fused
+                .partial_cmp(&a_fused)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+
+        if self.reranker.is_enabled() {
+            chunks.truncate(self.retrieval.rerank_candidates);
+
+            if let Err(error) = self.reranker.rerank(rerank_query, &mut chunks).await {
+                warn!(
+                    project_id = %project_id,
+                    "rerank failed, keeping fused order: {error:#}"
+                );
+            }
+        }
+
         let mut dedup = Vec::new();
         let mut seen = HashSet::new();
         let mut file_counter: HashMap<String, usize> = HashMap::new();
 
         for chunk in chunks {
-            let key = format!(
-                "{}:{}:{}:{}",
-                chunk.source,
-                chunk.chunk_index,
-                chunk.start_line.unwrap_or_default(),
-                chunk.end_line.unwrap_or_default()
-            );
-
-            if seen.contains(&key) {
+            if seen.contains(&chunk.id) {
                 continue;
             }
 
             let count = file_counter.entry(chunk.source.clone()).or_insert(0);
-            if *count >= 3 {
+            if *count >= self.retrieval.max_chunks_per_file {
                 continue;
             }
 
             *count += 1;
-            seen.insert(key);
+            seen.insert(chunk.id.clone());
             dedup.push(chunk);
 
-            if dedup.len() >= 14 {
+            if dedup.len() >= self.retrieval.max_chunks {
                 break;
             }
         }
@@ -156,6 +569,30 @@ impl RagEngine {
     }
 }
 
+/// Favours chunks from the relevant files and symbols the page or question mentions by name.
+fn boosted_score(
+    chunk: &RetrievedChunk,
+    relevant_paths: &HashSet<String>,
+    mention_text: &str,
+) -> f32 {
+    let mut score = chunk.score;
+
+    if relevant_paths.contains(&chunk.source) {
+        score += RELEVANT_FILE_BOOST;
+    }
+
+    let mentioned = chunk.symbol.as_ref().is_some_and(|symbol| {
+        let short_name = symbol.name.rsplit("::").next().unwrap_or(&symbol.name);
+        short_name.len() >= 3 && mention_text.contains(&short_name.to_lowercase())
+    });
+
+    if mentioned {
+        score += SYMBOL_MATCH_BOOST;
+    }
+
+    score
+}
+
 fn build_query(section_title: &str, page: &WikiPage) -> String {
     format!(
         "Section: {section}\n\nPage title: {title}\n\nPage description: {description}\n\nRelevant files:\n{files}",
@@ -197,6 +634,10 @@ fn build_chunk(
         "language".to_string(),
         serde_json::Value::String(classified.language.clone()),
     );
+    metadata.insert(
+        "contentHash".to_string(),
+        serde_json::Value::String(classified.content_hash.clone()),
+    );
     metadata.insert(
         "chunkIndex".to_string(),
         serde_json::Value::Number((chunk_index as u64).into()),
@@ -217,16 +658,63 @@ fn build_chunk(
     }
 
     Chunk {
-        id: Uuid::new_v4().to_string(),
+        id: chunk_point_id(classified, chunk_index).to_string(),
         text,
         metadata,
     }
 }
 
-fn split_chunks(classified: &ClassifiedFile, content: &str) -> Vec<Chunk> {
-    let splits = split_text(classified, content);
+/// Derives a stable point ID so re-indexing identical content upserts in place.
+fn chunk_point_id(classified: &ClassifiedFile, chunk_index: usize) -> Uuid {
+    let name = format!(
+        "{}:{}:{}",
+        classified.relative_path, chunk_index, classified.content_hash
+    );
+
+    Uuid::new_v5(&classified.project_id, name.as_bytes())
+}
+
+fn build_symbol_chunk(classified: &ClassifiedFile, chunk_index: usize, symbol: Symbol) -> Chunk {
+    let mut text = format!("{} {}\n\n{}", symbol.kind, symbol.name, symbol.signature);
+    if let Some(doc) = &symbol.doc {
+        text.push_str("\n\n");
+        text.push_str(doc);
+    }
+    text.push_str("\n\n");
+    text.push_str(&symbol.body);
+
+    let mut chunk = build_chunk(
+        classified,
+        chunk_index,
+        text,
+        Some(symbol.start_line),
+        Some(symbol.end_line),
+    );
+
+    chunk.metadata.insert(
+        "kind".to_string(),
+        serde_json::Value::String("symbol".to_string()),
+    );
+    chunk.metadata.insert(
+        "symbolName".to_string(),
+        serde_json::Value::String(symbol.name),
+    );
+    chunk.metadata.insert(
+        "symbolKind".to_string(),
+        serde_json::Value::String(symbol.kind.to_string()),
+    );
+    chunk.metadata.insert(
+        "signature".to_string(),
+        serde_json::Value::String(symbol.signature),
+    );
+
+    chunk
+}
+
+fn split_chunks(classified: &ClassifiedFile, content: &str, sizes: ChunkSizes) -> Vec<Chunk> {
+    let splits = split_text(classified, content, sizes);
 
-    splits
+    let mut chunks = splits
         .into_iter()
         .enumerate()
         .map(|(index, split)| {
@@ -238,5 +726,19 @@ fn split_chunks(classified: &ClassifiedFile, content: &str) -> Vec<Chunk> {
                 split.end_line,
             )
         })
-        .collect()
+        .collect::<Vec<_>>();
+
+    if classified.kind == "code" {
+        let offset = chunks.len();
+        let symbols = extract_symbols(classified, content);
+
+        chunks.extend(
+            symbols
+                .into_iter()
+                .enumerate()
+                .map(|(index, symbol)| build_symbol_chunk(classified, offset + index, symbol)),
+        );
+    }
+
+    chunks
 }
@@ -1,47 +1,249 @@
-use crate::models::{PreparedDoc, RetrievedChunk};
+use crate::models::{ChunkSymbol, PreparedDoc, RetrievedChunk};
+use crate::resilience::{CallError, RetryPolicy, Upstream, classify_error, is_retryable_status};
 use anyhow::{Result, anyhow};
-use reqwest::Client;
+use reqwest::{Client, Method, StatusCode};
 use serde_json::{Value, json};
+use std::collections::HashMap;
 use uuid::Uuid;
 
+const SCROLL_PAGE_SIZE: usize = 512;
+const DELETE_BATCH_SIZE: usize = 256;
+
+/// Collection used before collections were split per embedding model.
+pub const LEGACY_COLLECTION: &str = "wiki";
+
 #[derive(Clone)]
 pub struct QdrantClient {
     http: Client,
     base_url: String,
     collection_name: String,
+    vector_size: usize,
+    upstream: Upstream,
+    retry: RetryPolicy,
 }
 
 impl QdrantClient {
-    pub fn new(base_url: String) -> Self {
+    pub fn new(
+        base_url: String,
+        collection_name: String,
+        vector_size: usize,
+        upstream: Upstream,
+        retry: RetryPolicy,
+    ) -> Self {
         Self {
             http: Client::new(),
             base_url: base_url.trim_end_matches('/').to_string(),
-            collection_name: "wiki".to_string(),
+            collection_name,
+            vector_size,
+            upstream,
+            retry,
+        }
+    }
+
+    /// Same connection, different collection.
+    pub fn for_collection(&self, collection_name: String, vector_size: usize) -> Self {
+        Self {
+            http: self.http.clone(),
+            base_url: self.base_url.clone(),
+            collection_name,
+            vector_size,
+            upstream: self.upstream.clone(),
+            retry: self.retry,
         }
     }
 
+    pub fn collection_name(&self) -> &str {
+        &self.collection_name
+    }
+
+    pub fn vector_size(&self) -> usize {
+        self.vector_size
+    }
+
+    /// Creates the collection if missing and refuses to reuse one whose vectors
+    /// have a different dimension than the configured embedding model.
     pub async fn ensure_collection(&self) -> Result<()> {
         let collection_url = format!("{}/collections/{}", self.base_url, self.collection_name);
 
-        let exists = self.http.get(&collection_url).send().await?;
-        if exists.status().is_success() {
-            return Ok(());
+        let existing = self
+            .send_optional(
+                "fetch qdrant collection",
+                Method::GET,
+                &collection_url,
+                None,
+            )
+            .await?;
+
+        if let Some(payload) = existing {
+            let existing_size = payload
+                .pointer("/result/config/params/vectors/size")
+                .and_then(Value::as_u64)
+                .map(|size| size as usize);
+
+            return match existing_size {
+                Some(size) if size == self.vector_size => Ok(()),
+                Some(size) => Err(anyhow!(
+                    "qdrant collection {} stores {size}-dimension vectors but the embedding model produces {}",
+                    self.collection_name,
+                    self.vector_size
+                )),
+                None => Err(anyhow!(
+                    "qdrant collection {} has no single unnamed vector configuration",
+                    self.collection_name
+                )),
+            };
         }
 
         let body = json!({
             "vectors": {
-                "size": 768,
+                "size": self.vector_size,
                 "distance": "Cosine"
             }
         });
 
-        let response = self.http.put(&collection_url).json(&body).send().await?;
-        if !response.status().is_success() {
-            let status = response.status();
-            let response_body = response.text().await.unwrap_or_default();
-            return Err(anyhow!(
-                "failed to create qdrant collection ({status}): {response_body}"
-            ));
+        self.send(
+            "create qdrant collection",
+            Method::PUT,
+            &collection_url,
+            Some(&body),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn fetch_file_hashes(&self, project_id: Uuid) -> Result<HashMap<String, String>> {
+        let points = self
+            .scroll_points(
+                project_id,
+                json!(["metadata.source", "metadata.contentHash"]),
+            )
+            .await?;
+
+        let mut hashes = HashMap::new();
+        for point in points {
+            let metadata = point
+                .pointer("/payload/metadata")
+                .cloned()
+                .unwrap_or_else(|| json!({}));
+
+            let Some(source) = metadata.get("source").and_then(Value::as_str) else {
+                continue;
+            };
+
+            let hash = metadata
+                .get("contentHash")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+
+            hashes.insert(source.replace('\\', "/"), hash.to_string());
+        }
+
+        Ok(hashes)
+    }
+
+    /// Returns `(point id, chunk text)` for every point of the project.
+    pub async fn fetch_contents(&self, project_id: Uuid) -> Result<Vec<(String, String)>> {
+        let points = self
+            .scroll_points(project_id, json!(["pageContent"]))
+            .await?;
+
+        Ok(points
+            .iter()
+            .filter_map(|point| {
+                let content = point
+                    .pointer("/payload/pageContent")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_string();
+
+                Some((point_id(point)?, content))
+            })
+            .collect())
+    }
+
+    /// Returns `(point id, chunk text, metadata)` for every point of the project.
+    pub async fn fetch_documents(
+        &self,
+        project_id: Uuid,
+    ) -> Result<Vec<(String, String, HashMap<String, Value>)>> {
+        let points = self.scroll_points(project_id, json!(true)).await?;
+
+        Ok(points
+            .iter()
+            .filter_map(|point| {
+                let content = point
+                    .pointer("/payload/pageContent")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_string();
+
+                let metadata = point
+                    .pointer("/payload/metadata")
+                    .and_then(Value::as_object)
+                    .map(|object| {
+                        object
+                            .iter()
+                            .map(|(key, value)| (key.clone(), value.clone()))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+
+                Some((point_id(point)?, content, metadata))
+            })
+            .collect())
+    }
+
+    pub async fn fetch_points(&self, ids: &[String]) -> Result<Vec<RetrievedChunk>> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let url = format!(
+            "{}/collections/{}/points",
+            self.base_url, self.collection_name
+        );
+
+        let body = json!({
+            "ids": ids,
+            "with_payload": true,
+            "with_vector": false
+        });
+
+        let payload = self
+            .send("fetch qdrant points", Method::POST, &url, Some(&body))
+            .await?;
+        Ok(payload
+            .get("result")
+            .and_then(Value::as_array)
+            .map(|items| items.iter().filter_map(parse_point).collect())
+            .unwrap_or_default())
+    }
+
+    pub async fn delete_file_vectors(&self, project_id: Uuid, sources: &[String]) -> Result<()> {
+        let url = format!(
+            "{}/collections/{}/points/delete?wait=true",
+            self.base_url, self.collection_name
+        );
+
+        for batch in sources.chunks(DELETE_BATCH_SIZE) {
+            let body = json!({
+                "filter": {
+                    "must": [
+                        {
+                            "key": "metadata.projectId",
+                            "match": { "value": project_id }
+                        },
+                        {
+                            "key": "metadata.source",
+                            "match": { "any": batch }
+                        }
+                    ]
+                }
+            });
+
+            self.send("delete qdrant points", Method::POST, &url, Some(&body))
+                .await?;
         }
 
         Ok(())
@@ -64,14 +266,8 @@ impl QdrantClient {
             }
         });
 
-        let response = self.http.post(url).json(&body).send().await?;
-        if !response.status().is_success() {
-            let status = response.status();
-            let response_body = response.text().await.unwrap_or_default();
-            return Err(anyhow!(
-                "failed to delete qdrant points ({status}): {response_body}"
-            ));
-        }
+        self.send("delete qdrant points", Method::POST, &url, Some(&body))
+            .await?;
 
         Ok(())
     }
@@ -101,14 +297,8 @@ impl QdrantClient {
             .collect::<Vec<_>>();
 
         let body = json!({ "points": points });
-        let response = self.http.put(url).json(&body).send().await?;
-        if !response.status().is_success() {
-            let status = response.status();
-            let response_body = response.text().await.unwrap_or_default();
-            return Err(anyhow!(
-                "failed to upsert qdrant points ({status}): {response_body}"
-            ));
-        }
+        self.send("upsert qdrant points", Method::PUT, &url, Some(&body))
+            .await?;
 
         Ok(())
     }
@@ -138,73 +328,232 @@ impl QdrantClient {
             }
         });
 
-        let response = self.http.post(url).json(&body).send().await?;
-        if !response.status().is_success() {
-            let status = response.status();
-            let response_body = response.text().await.unwrap_or_default();
-            return Err(anyhow!("qdrant search failed ({status}): {response_body}"));
-        }
-
-        let payload: Value = response.json().await?;
+        let payload = self
+            .send("search qdrant points", Method::POST, &url, Some(&body))
+            .await?;
         let result = payload
             .get("result")
             .and_then(Value::as_array)
             .cloned()
             .unwrap_or_default();
 
-        let mut chunks = Vec::new();
-        for item in result {
-            let score = item.get("score").and_then(Value::as_f64).unwrap_or(0.0) as f32;
+        Ok(result.iter().filter_map(parse_point).collect())
+    }
 
-            let payload = item.get("payload").cloned().unwrap_or_else(|| json!({}));
-            let metadata = payload
-                .get("metadata")
-                .cloned()
-                .unwrap_or_else(|| json!({}));
+    async fn scroll_points(&self, project_id: Uuid, with_payload: Value) -> Result<Vec<Value>> {
+        let url = format!(
+            "{}/collections/{}/points/scroll",
+            self.base_url, self.collection_name
+        );
 
-            let content = payload
-                .get("pageContent")
-                .and_then(Value::as_str)
-                .unwrap_or_default()
-                .to_string();
+        let mut points = Vec::new();
+        let mut offset = Value::Null;
 
-            let source = metadata
-                .get("source")
-                .and_then(Value::as_str)
-                .unwrap_or("unknown")
-                .replace('\\', "/");
-
-            let chunk = RetrievedChunk {
-                content,
-                source,
-                kind: metadata
-                    .get("kind")
-                    .and_then(Value::as_str)
-                    .unwrap_or("text")
-                    .to_string(),
-                language: metadata
-                    .get("language")
-                    .and_then(Value::as_str)
-                    .unwrap_or("none")
-                    .to_string(),
-                chunk_index: metadata
-                    .get("chunkIndex")
-                    .and_then(Value::as_u64)
-                    .unwrap_or(0) as usize,
-                start_line: metadata
-                    .get("startLine")
-                    .and_then(Value::as_u64)
-                    .map(|value| value as usize),
-                end_line: metadata
-                    .get("endLine")
-                    .and_then(Value::as_u64)
-                    .map(|value| value as usize),
-                score,
-            };
+        loop {
+            let mut body = json!({
+                "limit": SCROLL_PAGE_SIZE,
+                "with_payload": with_payload,
+                "with_vector": false,
+                "filter": {
+                    "must": [
+                        {
+                            "key": "metadata.projectId",
+                            "match": { "value": project_id }
+                        }
+                    ]
+                }
+            });
 
-            chunks.push(chunk);
+            if !offset.is_null() {
+                body["offset"] = offset;
+            }
+
+            let payload = self
+                .send("scroll qdrant points", Method::POST, &url, Some(&body))
+                .await?;
+            let result = payload.get("result").cloned().unwrap_or_else(|| json!({}));
+
+            if let Some(page) = result.get("points").and_then(Value::as_array) {
+                points.extend(page.iter().cloned());
+            }
+
+            offset = result
+                .get("next_page_offset")
+                .cloned()
+                .unwrap_or(Value::Null);
+            if offset.is_null() {
+                break;
+            }
         }
 
-        Ok(chunks)
+        Ok(points)
     }
+
+    async fn send(
+        &self,
+        operation: &str,
+        method: Method,
+        url: &str,
+        body: Option<&Value>,
+    ) -> Result<Value> {
+        self.execute(operation, method, url, body, false)
+            .await?
+            .ok_or_else(|| anyhow!("failed to {operation}: not found"))
+    }
+
+    async fn send_optional(
+        &self,
+        operation: &str,
+        method: Method,
+        url: &str,
+        body: Option<&Value>,
+    ) -> Result<Option<Value>> {
+        self.execute(operation, method, url, body, true).await
+    }
+
+    /// Sends one request through the resilience layer and parses the JSON body.
+    /// A 404 yields `None` when `allow_missing` is set.
+    async fn execute(
+        &self,
+        operation: &str,
+        method: Method,
+        url: &str,
+        body: Option<&Value>,
+        allow_missing: bool,
+    ) -> Result<Option<Value>> {
+        self.upstream
+            .call(operation, &self.retry, || {
+                let mut request = self.http.request(method.clone(), url);
+                if let Some(body) = body {
+                    request = request.json(body);
+                }
+
+                async move {
+                    let response = request.send().await.map_err(classify_error)?;
+                    let status = response.status();
+
+                    if allow_missing && status == StatusCode::NOT_FOUND {
+                        return Ok(None);
+                    }
+
+                    if !status.is_success() {
+                        let response_body = response.text().await.unwrap_or_default();
+                        let error = anyhow!("failed to {operation} ({status}): {response_body}");
+                        return Err(if is_retryable_status(status.as_u16()) {
+                            CallError::Retryable(error)
+                        } else {
+                            CallError::Permanent(error)
+                        });
+                    }
+
+                    response
+                        .json::<Value>()
+                        .await
+                        .map(Some)
+                        .map_err(classify_error)
+                }
+            })
+            .await
+    }
+}
+
+/// Derives a collection name from the embedding model and dimension, e.g.
+/// `wiki_nomic_embed_text_v2_moe_latest_768`.
+pub fn collection_name_for(embed_model: &str, dimension: usize) -> String {
+    let model = embed_model
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>()
+        .split('_')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("_");
+
+    format!("{LEGACY_COLLECTION}_{model}_{dimension}")
+}
+
+/// Qdrant ids are UUID strings or unsigned integers; anything else is not a point.
+fn point_id(item: &Value) -> Option<String> {
+    match item.get("id")? {
+        Value::String(value) => Some(value.clone()),
+        Value::Number(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+fn parse_point(item: &Value) -> Option<RetrievedChunk> {
+    let id = point_id(item)?;
+    let score = item.get("score").and_then(Value::as_f64).unwrap_or(0.0) as f32;
+
+    let payload = item.get("payload").cloned().unwrap_or_else(|| json!({}));
+    let metadata = payload
+        .get("metadata")
+        .cloned()
+        .unwrap_or_else(|| json!({}));
+
+    let content = payload
+        .get("pageContent")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string();
+
+    let source = metadata
+        .get("source")
+        .and_then(Value::as_str)
+        .unwrap_or("unknown")
+        .replace('\\', "/");
+
+    let symbol = metadata
+        .get("symbolName")
+        .and_then(Value::as_str)
+        .map(|name| ChunkSymbol {
+            name: name.to_string(),
+            kind: metadata
+                .get("symbolKind")
+                .and_then(Value::as_str)
+                .unwrap_or("symbol")
+                .to_string(),
+            signature: metadata
+                .get("signature")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+        });
+
+    Some(RetrievedChunk {
+        id,
+        content,
+        source,
+        kind: metadata
+            .get("kind")
+            .and_then(Value::as_str)
+            .unwrap_or("text")
+            .to_string(),
+        language: metadata
+            .get("language")
+            .and_then(Value::as_str)
+            .unwrap_or("none")
+            .to_string(),
+        chunk_index: metadata
+            .get("chunkIndex")
+            .and_then(Value::as_u64)
+            .unwrap_or(0) as usize,
+        start_line: metadata
+            .get("startLine")
+            .and_then(Value::as_u64)
+            .map(|value| value as usize),
+        end_line: metadata
+            .get("endLine")
+            .and_then(Value::as_u64)
+            .map(|value| value as usize),
+        symbol,
+        score,
+        rerank_score: None,
+    })
 }
@@ -0,0 +1,56 @@
+use crate::models::ProjectIndex;
+use crate::storage::write_atomic;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::fs;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+/// Records which collection and embedding model each project was indexed with.
+#[derive(Clone)]
+pub struct IndexRegistry {
+    path: PathBuf,
+    entries: Arc<RwLock<HashMap<Uuid, ProjectIndex>>>,
+}
+
+impl IndexRegistry {
+    pub async fn load(path: PathBuf) -> Result<Self> {
+        let entries = match fs::read(&path).await {
+            Ok(raw) => serde_json::from_slice(&raw)
+                .with_context(|| format!("invalid index registry {}", path.display()))?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(error) => return Err(error.into()),
+        };
+
+        Ok(Self {
+            path,
+            entries: Arc::new(RwLock::new(entries)),
+        })
+    }
+
+    pub async fn get(&self, project_id: Uuid) -> Option<ProjectIndex> {
+        self.entries.read().await.get(&project_id).cloned()
+    }
+
+    /// Points the project at a new collection. The file is replaced through a
+    /// rename, so readers see either the old or the new registry.
+    pub async fn set(&self, project_id: Uuid, entry: ProjectIndex) -> Result<()> {
+        let mut entries = self.entries.write().await;
+
+        let mut updated = entries.clone();
+        updated.insert(project_id, entry);
+        self.persist(&updated).await?;
+
+        *entries = updated;
+        Ok(())
+    }
+
+    async fn persist(&self, entries: &HashMap<Uuid, ProjectIndex>) -> Result<()> {
+        let serialized = serde_json::to_vec_pretty(entries)?;
+        write_atomic(&self.path, serialized)
+            .await
+            .context("failed to write index registry")
+    }
+}
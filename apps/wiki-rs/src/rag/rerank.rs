@@ -0,0 +1,214 @@
+use crate::config::GenerationParams;
+use crate::llm::LlmClient;
+use crate::models::RetrievedChunk;
+use crate::prompts::PromptStore;
+use crate::resilience::{CallError, RetryPolicy, Upstream, classify_error, is_retryable_status};
+use anyhow::{Result, anyhow};
+use reqwest::Client;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::json;
+
+const CANDIDATE_MAX_CHARS: usize = 600;
+const LLM_MAX_SCORE: f32 = 10.0;
+
+/// Optional second-stage scoring of retrieved chunks against the page being written.
+#[derive(Clone)]
+pub enum Reranker {
+    Disabled,
+    Llm {
+        llm: Box<LlmClient>,
+        prompts: PromptStore,
+        params: GenerationParams,
+    },
+    Endpoint(EndpointReranker),
+}
+
+/// A `/rerank` endpoint in the Cohere/Jina style, on the LLM server.
+#[derive(Clone)]
+pub struct EndpointReranker {
+    http: Client,
+    url: String,
+    model: String,
+    api_key: Option<String>,
+    upstream: Upstream,
+    retry: RetryPolicy,
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct LlmRerankOutput {
+    scores: Vec<LlmRerankScore>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct LlmRerankScore {
+    index: usize,
+    score: f32,
+}
+
+#[derive(Deserialize)]
+struct EndpointRerankOutput {
+    results: Vec<EndpointRerankResult>,
+}
+
+#[derive(Deserialize)]
+struct EndpointRerankResult {
+    index: usize,
+    relevance_score: f32,
+}
+
+impl Reranker {
+    pub fn endpoint(
+        base_url: &str,
+        model: String,
+        api_key: Option<String>,
+        upstream: Upstream,
+        retry: RetryPolicy,
+    ) -> Result<Self> {
+        let http = Client::builder().timeout(retry.timeout).build()?;
+
+        Ok(Self::Endpoint(EndpointReranker {
+            http,
+            url: format!("{}/rerank", base_url.trim_end_matches('/')),
+            model,
+            api_key,
+            upstream,
+            retry,
+        }))
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !matches!(self, Self::Disabled)
+    }
+
+    /// Fills `rerank_score` on each chunk and reorders them by it. Chunks the
+    /// reranker did not score keep their relative order after the scored ones.
+    pub async fn rerank(&self, query: &str, chunks: &mut [RetrievedChunk]) -> Result<()> {
+        if chunks.is_empty() {
+            return Ok(());
+        }
+
+        let scores = match self {
+            Self::Disabled => return Ok(()),
+            Self::Llm {
+                llm,
+                prompts,
+                params,
+            } => score_with_llm(llm, prompts, *params, query, chunks).await?,
+            Self::Endpoint(endpoint) => endpoint.score(query, chunks).await?,
+        };
+
+        for (index, score) in scores {
+            if let Some(chunk) = chunks.get_mut(index) {
+                chunk.rerank_score = Some(score);
+            }
+        }
+
+        chunks.sort_by(|a, b| match (a.rerank_score, b.rerank_score) {
+            (Some(a), Some(b)) => b.partial_cmp(&a).unwrap_or(std::cmp::Ordering::Equal),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => std::cmp::Ordering::Equal,
+        });
+
+        Ok(())
+    }
+}
+
+async fn score_with_llm(
+    llm: &LlmClient,
+    prompts: &PromptStore,
+    params: GenerationParams,
+    query: &str,
+    chunks: &[RetrievedChunk],
+) -> Result<Vec<(usize, f32)>> {
+    let candidates = chunks
+        .iter()
+        .enumerate()
+        .map(|(index, chunk)| {
+            format!(
+                "[CANDIDATE {index} | {}]\n{}\n[/CANDIDATE]",
+                chunk.source,
+                truncate(&chunk.content, CANDIDATE_MAX_CHARS)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    let prompt = prompts.render(
+        "reranker/reranker",
+        &[("query", query.to_string()), ("candidates", candidates)],
+    )?;
+
+    let output = llm
+        .chat_structured::<LlmRerankOutput>(
+            &prompt,
+            "rerank_scores",
+            params.temperature,
+            Some(params.max_tokens),
+        )
+        .await?;
+
+    Ok(output
+        .scores
+        .into_iter()
+        .map(|item| (item.index, (item.score / LLM_MAX_SCORE).clamp(0.0, 1.0)))
+        .collect())
+}
+
+impl EndpointReranker {
+    async fn score(&self, query: &str, chunks: &[RetrievedChunk]) -> Result<Vec<(usize, f32)>> {
+        let documents = chunks
+            .iter()
+            .map(|chunk| chunk.content.as_str())
+            .collect::<Vec<_>>();
+
+        let body = json!({
+            "model": self.model,
+            "query": query,
+            "documents": documents,
+        });
+
+        let output = self
+            .upstream
+            .call("rerank", &self.retry, || {
+                let mut request = self.http.post(&self.url).json(&body);
+                if let Some(api_key) = &self.api_key {
+                    request = request.bearer_auth(api_key);
+                }
+
+                async move {
+                    let response = request.send().await.map_err(classify_error)?;
+                    let status = response.status();
+                    if !status.is_success() {
+                        let response_body = response.text().await.unwrap_or_default();
+                        let error = anyhow!("rerank request failed ({status}): {response_body}");
+                        return Err(if is_retryable_status(status.as_u16()) {
+                            CallError::Retryable(error)
+                        } else {
+                            CallError::Permanent(error)
+                        });
+                    }
+
+                    response
+                        .json::<EndpointRerankOutput>()
+                        .await
+                        .map_err(classify_error)
+                }
+            })
+            .await?;
+
+        Ok(output
+            .results
+            .into_iter()
+            .map(|item| (item.index, item.relevance_score))
+            .collect())
+    }
+}
+
+fn truncate(value: &str, max_chars: usize) -> &str {
+    match value.char_indices().nth(max_chars) {
+        Some((index, _)) => &value[..index],
+        None => value,
+    }
+}
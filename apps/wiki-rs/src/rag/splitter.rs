@@ -2,6 +2,24 @@ use crate::rag::classifier::ClassifiedFile;
 use text_splitter::{ChunkConfig, CodeSplitter, MarkdownSplitter, TextSplitter};
 use tree_sitter::Language;
 
+/// Target chunk sizes, in characters, per kind of file.
+#[derive(Clone, Copy, Debug)]
+pub struct ChunkSizes {
+    pub markdown: usize,
+    pub code: usize,
+    pub text: usize,
+}
+
+impl ChunkSizes {
+    pub fn for_kind(&self, kind: &str) -> usize {
+        match kind {
+            "markdown" => self.markdown,
+            "code" => self.code,
+            _ => self.text,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct SplitChunk {
     pub text: String,
@@ -9,55 +27,82 @@ pub struct SplitChunk {
     pub end_line: Option<usize>,
 }
 
-pub fn split_document(classified: &ClassifiedFile, content: &str) -> Vec<SplitChunk> {
+pub fn split_document(
+    classified: &ClassifiedFile,
+    content: &str,
+    sizes: ChunkSizes,
+) -> Vec<SplitChunk> {
     if content.trim().is_empty() {
         return Vec::new();
     }
 
-    let chunks = split_with_library(classified, content)
-        .into_iter()
-        .map(|chunk| chunk.trim().to_string())
-        .filter(|chunk| !chunk.is_empty())
-        .collect::<Vec<_>>();
+    let line_starts = line_starts(content);
 
-    chunks
+    split_with_library(classified, content, sizes)
         .into_iter()
-        .map(|text| SplitChunk {
-            text,
-            start_line: None,
-            end_line: None,
+        .filter_map(|(offset, chunk)| {
+            let text = chunk.trim();
+            if text.is_empty() {
+                return None;
+            }
+
+            let start = offset + (chunk.len() - chunk.trim_start().len());
+            let end = start + text.len();
+
+            Some(SplitChunk {
+                text: text.to_string(),
+                start_line: Some(line_at(&line_starts, start)),
+                end_line: Some(line_at(&line_starts, end - 1)),
+            })
         })
         .collect()
 }
 
-fn split_with_library(classified: &ClassifiedFile, content: &str) -> Vec<String> {
+fn split_with_library<'a>(
+    classified: &ClassifiedFile,
+    content: &'a str,
+    sizes: ChunkSizes,
+) -> Vec<(usize, &'a str)> {
+    let size = sizes.for_kind(&classified.kind);
     match classified.kind.as_str() {
         "markdown" => {
-            let splitter = MarkdownSplitter::new(ChunkConfig::new(1200));
-            splitter.chunks(content).map(str::to_string).collect()
+            let splitter = MarkdownSplitter::new(ChunkConfig::new(size));
+            splitter.chunk_indices(content).collect()
         }
         "code" => {
             let Some(language) = map_tree_sitter_language(&classified.language) else {
-                let fallback = TextSplitter::new(ChunkConfig::new(800));
-                return fallback.chunks(content).map(str::to_string).collect();
+                let fallback = TextSplitter::new(ChunkConfig::new(size));
+                return fallback.chunk_indices(content).collect();
             };
 
-            match CodeSplitter::new(language, ChunkConfig::new(800)) {
-                Ok(splitter) => splitter.chunks(content).map(str::to_string).collect(),
+            match CodeSplitter::new(language, ChunkConfig::new(size)) {
+                Ok(splitter) => splitter.chunk_indices(content).collect(),
                 Err(_) => {
-                    let fallback = TextSplitter::new(ChunkConfig::new(800));
-                    fallback.chunks(content).map(str::to_string).collect()
+                    let fallback = TextSplitter::new(ChunkConfig::new(size));
+                    fallback.chunk_indices(content).collect()
                 }
             }
         }
         _ => {
-            let splitter = TextSplitter::new(ChunkConfig::new(1000));
-            splitter.chunks(content).map(str::to_string).collect()
+            let splitter = TextSplitter::new(ChunkConfig::new(size));
+            splitter.chunk_indices(content).collect()
         }
     }
 }
 
-fn map_tree_sitter_language(value: &str) -> Option<Language> {
+/// Byte offsets at which each line of `content` begins.
+fn line_starts(content: &str) -> Vec<usize> {
+    std::iter::once(0)
+        .chain(content.match_indices('\n').map(|(index, _)| index + 1))
+        .collect()
+}
+
+/// 1-based line number containing the byte at `offset`.
+fn line_at(line_starts: &[usize], offset: usize) -> usize {
+    line_starts.partition_point(|start| *start <= offset)
+}
+
+pub fn map_tree_sitter_language(value: &str) -> Option<Language> {
     match value {
         "python" => Some(tree_sitter_python::LANGUAGE.into()),
         "javascript" | "javascript-react" => Some(tree_sitter_javascript::LANGUAGE.into()),
@@ -70,3 +115,65 @@ fn map_tree_sitter_language(value: &str) -> Option<Language> {
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    const SIZES: ChunkSizes = ChunkSizes {
+        markdown: 2000,
+        code: 2000,
+        text: 2000,
+    };
+
+    fn file(kind: &str) -> ClassifiedFile {
+        ClassifiedFile {
+            project_id: Uuid::nil(),
+            relative_path: "notes.txt".to_string(),
+            kind: kind.to_string(),
+            language: "text".to_string(),
+            content_hash: String::new(),
+        }
+    }
+
+    fn lines(content: &str) -> Vec<(Option<usize>, Option<usize>)> {
+        split_document(&file("text"), content, SIZES)
+            .into_iter()
+            .map(|chunk| (chunk.start_line, chunk.end_line))
+            .collect()
+    }
+
+    #[test]
+    fn line_at_counts_from_one_and_includes_the_newline() {
+        let starts = line_starts("ab\ncd\n\nef");
+        assert_eq!(starts, vec![0, 3, 6, 7]);
+
+        assert_eq!(line_at(&starts, 0), 1);
+        assert_eq!(line_at(&starts, 2), 1);
+        assert_eq!(line_at(&starts, 3), 2);
+        assert_eq!(line_at(&starts, 6), 3);
+        assert_eq!(line_at(&starts, 7), 4);
+        assert_eq!(line_at(&starts, 8), 4);
+    }
+
+    #[test]
+    fn a_single_line_spans_one_line() {
+        assert_eq!(lines("fn main() {}"), vec![(Some(1), Some(1))]);
+    }
+
+    #[test]
+    fn a_trailing_newline_does_not_extend_the_range() {
+        assert_eq!(lines("one\ntwo\n"), vec![(Some(1), Some(2))]);
+    }
+
+    #[test]
+    fn surrounding_blank_lines_are_not_counted() {
+        assert_eq!(lines("\n\n  one\ntwo\n\n\n"), vec![(Some(3), Some(4))]);
+    }
+
+    #[test]
+    fn blank_documents_have_no_chunks() {
+        assert!(lines(" \n\t\n").is_empty());
+    }
+}
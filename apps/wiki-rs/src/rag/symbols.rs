@@ -0,0 +1,267 @@
+use crate::rag::classifier::ClassifiedFile;
+use crate::rag::splitter::map_tree_sitter_language;
+use tree_sitter::{Node, Parser};
+
+const SIGNATURE_MAX_CHARS: usize = 300;
+const DOC_MAX_CHARS: usize = 1000;
+const BODY_MAX_CHARS: usize = 1500;
+
+#[derive(Clone, Debug)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: &'static str,
+    pub signature: String,
+    pub doc: Option<String>,
+    pub body: String,
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+enum NodeRole {
+    Symbol(&'static str),
+    Container(String),
+    Other,
+}
+
+pub fn extract_symbols(classified: &ClassifiedFile, content: &str) -> Vec<Symbol> {
+    let Some(language) = map_tree_sitter_language(&classified.language) else {
+        return Vec::new();
+    };
+
+    let mut parser = Parser::new();
+    if parser.set_language(&language).is_err() {
+        return Vec::new();
+    }
+
+    let Some(tree) = parser.parse(content, None) else {
+        return Vec::new();
+    };
+
+    let mut symbols = Vec::new();
+    collect_symbols(tree.root_node(), content, None, &mut symbols);
+    symbols
+}
+
+fn collect_symbols(node: Node, source: &str, container: Option<&str>, symbols: &mut Vec<Symbol>) {
+    let mut cursor = node.walk();
+
+    for child in node.named_children(&mut cursor) {
+        match classify_node(&child, source) {
+            NodeRole::Symbol(kind) => {
+                let kind = match (kind, container) {
+                    ("function", Some(_)) => "method",
+                    _ => kind,
+                };
+
+                let Some(symbol) = build_symbol(child, kind, source, container) else {
+                    continue;
+                };
+
+                let name = symbol.name.clone();
+                symbols.push(symbol);
+
+                if is_container_kind(kind) {
+                    collect_symbols(child, source, Some(&name), symbols);
+                }
+            }
+            NodeRole::Container(name) => {
+                let qualified = qualify(container, &name);
+                collect_symbols(child, source, Some(&qualified), symbols);
+            }
+            NodeRole::Other => collect_symbols(child, source, container, symbols),
+        }
+    }
+}
+
+fn classify_node(node: &Node, source: &str) -> NodeRole {
+    let has_body = node.child_by_field_name("body").is_some();
+
+    let kind = match node.kind() {
+        "function_item"
+        | "function_definition"
+        | "function_declaration"
+        | "generator_function_declaration" => "function",
+        "method_declaration"
+        | "method_definition"
+        | "constructor_declaration"
+        | "function_signature_item"
+        | "method_signature" => "method",
+        "struct_item" => "struct",
+        "struct_specifier" if has_body => "struct",
+        "enum_item" | "enum_declaration" => "enum",
+        "enum_specifier" if has_body => "enum",
+        "trait_item" => "trait",
+        "interface_declaration" => "interface",
+        "class_definition"
+        | "class_declaration"
+        | "abstract_class_declaration"
+        | "record_declaration" => "class",
+        "class_specifier" if has_body => "class",
+        "type_item" | "type_alias_declaration" => "type",
+        "type_spec" => match node.child_by_field_name("type").map(|inner| inner.kind()) {
+            Some("struct_type") => "struct",
+            Some("interface_type") => "interface",
+            _ => "type",
+        },
+        "impl_item" => {
+            return node
+                .child_by_field_name("type")
+                .and_then(|target| node_text(target, source))
+                .map(|name| NodeRole::Container(name.to_string()))
+                .unwrap_or(NodeRole::Other);
+        }
+        _ => return NodeRole::Other,
+    };
+
+    NodeRole::Symbol(kind)
+}
+
+fn is_container_kind(kind: &str) -> bool {
+    matches!(kind, "trait" | "interface" | "class" | "struct")
+}
+
+fn build_symbol(
+    node: Node,
+    kind: &'static str,
+    source: &str,
+    container: Option<&str>,
+) -> Option<Symbol> {
+    let name = symbol_name(node, source)?;
+    let text = node_text(node, source)?;
+
+    Some(Symbol {
+        name: qualify(container, &name),
+        kind,
+        signature: signature(node, source, text),
+        doc: doc_comment(node, source),
+        body: truncate(text, BODY_MAX_CHARS),
+        start_line: node.start_position().row + 1,
+        end_line: node.end_position().row + 1,
+    })
+}
+
+/// Resolves the symbol name, following C/C++ declarator chains when there is no `name` field.
+fn symbol_name(node: Node, source: &str) -> Option<String> {
+    if let Some(name) = node.child_by_field_name("name") {
+        return node_text(name, source).map(str::to_string);
+    }
+
+    let mut declarator = node.child_by_field_name("declarator")?;
+    while let Some(inner) = declarator.child_by_field_name("declarator") {
+        declarator = inner;
+    }
+
+    node_text(declarator, source).map(str::to_string)
+}
+
+/// Everything before the body, or the first line for declarations without one.
+fn signature(node: Node, source: &str, text: &str) -> String {
+    let head = match node.child_by_field_name("body") {
+        Some(body) => &source[node.start_byte()..body.start_byte()],
+        None => text.lines().next().unwrap_or_default(),
+    };
+
+    let collapsed = head.split_whitespace().collect::<Vec<_>>().join(" ");
+    let trimmed = collapsed.trim_end_matches(['{', ':', ' ']);
+    truncate(trimmed, SIGNATURE_MAX_CHARS)
+}
+
+fn doc_comment(node: Node, source: &str) -> Option<String> {
+    if let Some(docstring) = python_docstring(node, source) {
+        return Some(docstring);
+    }
+
+    let anchor = match node.parent() {
+        Some(parent) if matches!(parent.kind(), "export_statement" | "decorated_definition") => {
+            parent
+        }
+        _ => node,
+    };
+
+    let mut lines = Vec::new();
+    let mut expected_row = anchor.start_position().row;
+    let mut current = anchor.prev_named_sibling();
+
+    while let Some(sibling) = current {
+        if sibling.kind() == "attribute_item" {
+            expected_row = sibling.start_position().row;
+            current = sibling.prev_named_sibling();
+            continue;
+        }
+
+        if !sibling.kind().contains("comment") || sibling.end_position().row + 1 < expected_row {
+            break;
+        }
+
+        lines.push(node_text(sibling, source)?);
+        expected_row = sibling.start_position().row;
+        current = sibling.prev_named_sibling();
+    }
+
+    if lines.is_empty() {
+        return None;
+    }
+
+    lines.reverse();
+    clean_comment(&lines.join("\n"))
+}
+
+fn python_docstring(node: Node, source: &str) -> Option<String> {
+    if !matches!(node.kind(), "function_definition" | "class_definition") {
+        return None;
+    }
+
+    let statement = node.child_by_field_name("body")?.named_child(0)?;
+    if statement.kind() != "expression_statement" {
+        return None;
+    }
+
+    let string = statement.named_child(0)?;
+    if string.kind() != "string" {
+        return None;
+    }
+
+    let raw = node_text(string, source)?;
+    clean_comment(raw.trim_matches(|c| c == '"' || c == '\''))
+}
+
+fn clean_comment(raw: &str) -> Option<String> {
+    let cleaned = raw
+        .lines()
+        .map(|line| {
+            let line = line.trim();
+            let line = ["///", "//!", "//", "/**", "/*", "*", "#"]
+                .iter()
+                .find_map(|marker| line.strip_prefix(marker))
+                .unwrap_or(line);
+
+            line.trim_end_matches("*/").trim()
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let cleaned = cleaned.trim();
+    if cleaned.is_empty() {
+        None
+    } else {
+        Some(truncate(cleaned, DOC_MAX_CHARS))
+    }
+}
+
+fn qualify(container: Option<&str>, name: &str) -> String {
+    match container {
+        Some(container) => format!("{container}::{name}"),
+        None => name.to_string(),
+    }
+}
+
+fn node_text<'a>(node: Node, source: &'a str) -> Option<&'a str> {
+    node.utf8_text(source.as_bytes()).ok()
+}
+
+fn truncate(value: &str, max_chars: usize) -> String {
+    match value.char_indices().nth(max_chars) {
+        Some((index, _)) => format!("{}…", &value[..index]),
+        None => value.to_string(),
+    }
+}
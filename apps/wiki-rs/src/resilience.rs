@@ -0,0 +1,230 @@
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// Timeout and retry schedule for one kind of outbound call.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    pub timeout: Duration,
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct BreakerOptions {
+    pub failure_threshold: u32,
+    pub open_for: Duration,
+}
+
+/// Outcome of a single attempt, telling the retry loop whether another try can help.
+pub enum CallError {
+    Retryable(anyhow::Error),
+    Permanent(anyhow::Error),
+}
+
+/// One remote dependency (Ollama, Qdrant, ...) with its own circuit breaker
+/// and retry counters. Cloning shares the state.
+#[derive(Clone)]
+pub struct Upstream {
+    name: &'static str,
+    breaker: Arc<CircuitBreaker>,
+    metrics: Arc<UpstreamMetrics>,
+}
+
+struct CircuitBreaker {
+    options: BreakerOptions,
+    state: Mutex<BreakerState>,
+}
+
+#[derive(Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+}
+
+#[derive(Default)]
+struct UpstreamMetrics {
+    calls: AtomicU64,
+    retries: AtomicU64,
+    timeouts: AtomicU64,
+    failures: AtomicU64,
+    short_circuited: AtomicU64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UpstreamMetricsSnapshot {
+    pub upstream: &'static str,
+    pub circuit: &'static str,
+    pub calls: u64,
+    pub retries: u64,
+    pub timeouts: u64,
+    pub failures: u64,
+    pub short_circuited: u64,
+}
+
+impl Upstream {
+    pub fn new(name: &'static str, options: BreakerOptions) -> Self {
+        Self {
+            name,
+            breaker: Arc::new(CircuitBreaker {
+                options,
+                state: Mutex::new(BreakerState::default()),
+            }),
+            metrics: Arc::new(UpstreamMetrics::default()),
+        }
+    }
+
+    /// Runs `attempt` under the policy's timeout, retrying retryable failures
+    /// with jittered exponential backoff. Fails fast while the breaker is open.
+    pub async fn call<T, F, Fut>(
+        &self,
+        operation: &str,
+        policy: &RetryPolicy,
+        mut attempt: F,
+    ) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, CallError>>,
+    {
+        self.metrics.calls.fetch_add(1, Ordering::Relaxed);
+
+        if !self.breaker.allows() {
+            self.metrics.short_circuited.fetch_add(1, Ordering::Relaxed);
+            return Err(anyhow!(
+                "{} circuit breaker is open, skipping {operation}",
+                self.name
+            ));
+        }
+
+        let max_attempts = policy.max_attempts.max(1);
+        let mut attempt_number = 1;
+
+        loop {
+            let error = match tokio::time::timeout(policy.timeout, attempt()).await {
+                Ok(Ok(value)) => {
+                    self.breaker.record_success();
+                    return Ok(value);
+                }
+                Ok(Err(CallError::Permanent(error))) => {
+                    self.metrics.failures.fetch_add(1, Ordering::Relaxed);
+                    return Err(error);
+                }
+                Ok(Err(CallError::Retryable(error))) => error,
+                Err(_) => {
+                    self.metrics.timeouts.fetch_add(1, Ordering::Relaxed);
+                    anyhow!("{operation} timed out after {:?}", policy.timeout)
+                }
+            };
+
+            if attempt_number >= max_attempts || !self.breaker.allows() {
+                self.metrics.failures.fetch_add(1, Ordering::Relaxed);
+                self.breaker.record_failure();
+                return Err(error.context(format!(
+                    "{operation} failed after {attempt_number} attempt(s)"
+                )));
+            }
+
+            let delay = backoff_delay(policy, attempt_number);
+            warn!(
+                upstream = self.name,
+                operation,
+                attempt = attempt_number,
+                "call failed, retrying in {delay:?}: {error:#}"
+            );
+
+            self.metrics.retries.fetch_add(1, Ordering::Relaxed);
+            tokio::time::sleep(delay).await;
+            attempt_number += 1;
+        }
+    }
+
+    pub fn snapshot(&self) -> UpstreamMetricsSnapshot {
+        UpstreamMetricsSnapshot {
+            upstream: self.name,
+            circuit: self.breaker.state_name(),
+            calls: self.metrics.calls.load(Ordering::Relaxed),
+            retries: self.metrics.retries.load(Ordering::Relaxed),
+            timeouts: self.metrics.timeouts.load(Ordering::Relaxed),
+            failures: self.metrics.failures.load(Ordering::Relaxed),
+            short_circuited: self.metrics.short_circuited.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl CircuitBreaker {
+    /// Closed, or open long enough that a trial call may go through (half-open).
+    fn allows(&self) -> bool {
+        let state = self
+            .state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        state
+            .open_until
+            .is_none_or(|open_until| Instant::now() >= open_until)
+    }
+
+    fn record_success(&self) {
+        let mut state = self
+            .state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        *state = BreakerState::default();
+    }
+
+    fn record_failure(&self) {
+        let mut state = self
+            .state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        state.consecutive_failures += 1;
+
+        if state.consecutive_failures >= self.options.failure_threshold.max(1) {
+            state.open_until = Some(Instant::now() + self.options.open_for);
+        }
+    }
+
+    fn state_name(&self) -> &'static str {
+        let state = self
+            .state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        match state.open_until {
+            None => "closed",
+            Some(open_until) if Instant::now() < open_until => "open",
+            Some(_) => "half_open",
+        }
+    }
+}
+
+/// Full jitter: a random delay between zero and the capped exponential step.
+fn backoff_delay(policy: &RetryPolicy, attempt: u32) -> Duration {
+    let exponential = policy
+        .base_delay
+        .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)));
+    let capped = exponential.min(policy.max_delay);
+
+    let millis = capped.as_millis() as u64;
+    Duration::from_millis(rand::random_range(0..=millis))
+}
+
+/// Failures to reach the upstream or to read its answer are transient: timeouts,
+/// refused connections, requests cut off mid-flight and truncated bodies.
+/// Anything else, e.g. a body that is not the expected JSON, fails the same
+/// way again.
+pub fn classify_error(error: reqwest::Error) -> CallError {
+    if error.is_timeout() || error.is_connect() || error.is_request() || error.is_body() {
+        CallError::Retryable(error.into())
+    } else {
+        CallError::Permanent(error.into())
+    }
+}
+
+/// 408, 429 and 5xx are worth retrying; other statuses will fail the same way again.
+pub fn is_retryable_status(status: u16) -> bool {
+    matches!(status, 408 | 429) || (500..600).contains(&status)
+}
@@ -0,0 +1,141 @@
+use crate::models::{ChatSession, SessionMessage};
+use crate::storage::write_atomic;
+use anyhow::{Context, Result};
+use chrono::Utc;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::fs;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+/// Sessions stay in memory this long after they were last loaded or written;
+/// after that the next request reads them back from disk.
+const SESSION_CACHE_TTL: Duration = Duration::from_secs(30 * 60);
+
+/// Chat sessions persisted as one JSON file each, so they survive restarts.
+#[derive(Clone)]
+pub struct SessionStore {
+    dir: PathBuf,
+    cache: Arc<RwLock<HashMap<Uuid, CachedSession>>>,
+}
+
+struct CachedSession {
+    session: ChatSession,
+    cached_at: Instant,
+}
+
+impl SessionStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            cache: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    pub async fn create(&self, repo_id: Uuid) -> Result<ChatSession> {
+        let now = Utc::now();
+        let session = ChatSession {
+            id: Uuid::new_v4(),
+            repo_id,
+            created_at: now,
+            updated_at: now,
+            messages: Vec::new(),
+        };
+
+        let mut cache = self.cache.write().await;
+        self.persist(&session).await?;
+        cache_session(&mut cache, session.clone());
+
+        Ok(session)
+    }
+
+    pub async fn get(&self, session_id: Uuid) -> Result<Option<ChatSession>> {
+        if let Some(session) = cached(&*self.cache.read().await, session_id) {
+            return Ok(Some(session));
+        }
+
+        let mut cache = self.cache.write().await;
+        if let Some(session) = cached(&cache, session_id) {
+            return Ok(Some(session));
+        }
+
+        let Some(session) = self.read(session_id).await? else {
+            return Ok(None);
+        };
+
+        cache_session(&mut cache, session.clone());
+        Ok(Some(session))
+    }
+
+    /// Appends turns and persists the session. Holding the write lock keeps
+    /// concurrent turns on the same session from overwriting each other.
+    pub async fn append(
+        &self,
+        session_id: Uuid,
+        messages: Vec<SessionMessage>,
+    ) -> Result<Option<ChatSession>> {
+        let mut cache = self.cache.write().await;
+
+        let mut session = match cached(&cache, session_id) {
+            Some(session) => session,
+            None => match self.read(session_id).await? {
+                Some(session) => session,
+                None => return Ok(None),
+            },
+        };
+
+        session.messages.extend(messages);
+        session.updated_at = Utc::now();
+
+        self.persist(&session).await?;
+        cache_session(&mut cache, session.clone());
+
+        Ok(Some(session))
+    }
+
+    async fn read(&self, session_id: Uuid) -> Result<Option<ChatSession>> {
+        let path = self.session_path(session_id);
+        let raw = match fs::read(&path).await {
+            Ok(raw) => raw,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error.into()),
+        };
+
+        let session = serde_json::from_slice(&raw)
+            .with_context(|| format!("invalid chat session {}", path.display()))?;
+
+        Ok(Some(session))
+    }
+
+    async fn persist(&self, session: &ChatSession) -> Result<()> {
+        let serialized = serde_json::to_vec_pretty(session)?;
+        write_atomic(&self.session_path(session.id), serialized)
+            .await
+            .context("failed to write chat session")
+    }
+
+    fn session_path(&self, session_id: Uuid) -> PathBuf {
+        self.dir.join(format!("{session_id}.json"))
+    }
+}
+
+fn cached(cache: &HashMap<Uuid, CachedSession>, session_id: Uuid) -> Option<ChatSession> {
+    cache
+        .get(&session_id)
+        .filter(|entry| entry.cached_at.elapsed() < SESSION_CACHE_TTL)
+        .map(|entry| entry.session.clone())
+}
+
+/// Inserts the session and drops the expired ones.
+fn cache_session(cache: &mut HashMap<Uuid, CachedSession>, session: ChatSession) {
+    cache.retain(|_, entry| entry.cached_at.elapsed() < SESSION_CACHE_TTL);
+    cache.insert(
+        session.id,
+        CachedSession {
+            session,
+            cached_at: Instant::now(),
+        },
+    );
+}
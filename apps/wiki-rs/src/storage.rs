@@ -0,0 +1,28 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+/// Writes `bytes` beside `path` and renames the file over it, so a crash
+/// mid-write never leaves a truncated file behind. Creates the parent
+/// directory if needed.
+pub async fn write_atomic(path: &Path, bytes: impl AsRef<[u8]>) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+
+    let tmp_path = tmp_path(path);
+    fs::write(&tmp_path, bytes)
+        .await
+        .with_context(|| format!("failed to write {}", tmp_path.display()))?;
+    fs::rename(&tmp_path, path)
+        .await
+        .with_context(|| format!("failed to replace {}", path.display()))?;
+
+    Ok(())
+}
+
+fn tmp_path(path: &Path) -> PathBuf {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    PathBuf::from(tmp_path)
+}